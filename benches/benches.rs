@@ -1,35 +1,40 @@
-//! Bench module.
-#![feature(test)]
+//! Bench module: times whole games between AIs.
+//! Runs on stable with `cargo bench`, as a plain binary.
 
-extern crate reversi;
 extern crate rusthello_lib;
-extern crate test;
 
-use reversi::game::Game;
-use rusthello_lib::ai_player::AiPlayer;
+use rusthello_lib::OtherAction;
+use rusthello_lib::ai_player::{AiKind, AiPlayer};
+use rusthello_lib::custom_ai::SearchConfig;
+use rusthello_lib::game::{Game, IsPlayer};
+use std::time::Instant;
 
-#[bench]
-fn test_ai(b: &mut test::Bencher) {
+const RUNS: u32 = 10;
 
-    let adam = AiPlayer::Weak;
-    let eve  = AiPlayer::Weak;
-
-    b.iter(|| {
-               // First match: Adam is Dark, Eve is Light
-               let mut game = Game::new(&adam, &eve);
-
-               while !game.is_endgame() {
-                   game.play_turn().expect("`play_turn` returned an error");
-               }
-           });
+/// Plays a game to the end.
+fn play(dark: &dyn IsPlayer<OtherAction>, light: &dyn IsPlayer<OtherAction>) {
+    let mut game = Game::new(dark, light);
+    while !game.is_endgame() {
+        game.play_turn().expect("`play_turn` returned an error");
+    }
+}
 
-    b.iter(|| {
-               // Second match: Eve is Dark, Adam is Light
-               let mut game = Game::new(&eve, &adam);
+/// Runs `f` `RUNS` times and prints the average time it took.
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    for _ in 0 .. RUNS {
+        f();
+    }
+    let elapsed = start.elapsed() / RUNS;
+    println!("{:<12} {:>10.3} ms/iter", name, elapsed.as_secs() as f64 * 1e3 + elapsed.subsec_nanos() as f64 * 1e-6);
+}
 
-               while !game.is_endgame() {
-                   game.play_turn().expect("`play_turn` returned an error");
-               }
-           });
+fn main() {
+    let adam = AiPlayer::new(AiKind::Weak, SearchConfig::default());
+    let eve = AiPlayer::new(AiKind::Weak, SearchConfig::default());
 
+    // First match: Adam is Dark, Eve is Light
+    bench("adam_vs_eve", || play(&adam, &eve));
+    // Second match: Eve is Dark, Adam is Light
+    bench("eve_vs_adam", || play(&eve, &adam));
 }
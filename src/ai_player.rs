@@ -3,34 +3,51 @@ use {Result, Action};
 use rand::thread_rng;
 use rand::distributions::{IndependentSample, Range};
 use rayon::prelude::*;
-use reversi::{board, turn, game, Side, ReversiError};
-use reversi::board::Coord;
+use game::{Coord, IsPlayer, PlayerAction, ReversiError, Side, Turn};
+use std::cell::Cell;
 use std::cmp::Ordering;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use custom_ai;
+use custom_ai::{SearchConfig, SearchLimit, SearchObserver, SilentObserver};
+use transposition::TranspositionTable;
+use clock::Clock;
 
 const RANDOMNESS: f64 = 0.05f64;
 const WEAK:		u32 = 100;
 const MEDIUM:	u32 = 10000;
 const STRONG:	u32 = 1000000;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy)]
 pub enum Score {
     Running(f64),
     Ended(i16),
 }
 
+/// Running scores are ordered as if they were within this bound.
+const RUNNING_BOUND: f64 = 1e9;
+
+impl Score {
+    /// The key of the order of scores: a won (resp. lost) game is better (resp. worse)
+    /// than any running one, and a drawn game is worth as much as an even position.
+    fn key(self) -> f64 {
+        match self {
+            // Adding zero makes -0.0 equal to 0.0
+            Score::Running(val) => val.clamp(-RUNNING_BOUND, RUNNING_BOUND) + 0.0,
+            Score::Ended(diff) => diff as f64 + diff.signum() as f64 * 2.0 * RUNNING_BOUND,
+        }
+    }
+}
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Score) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
 impl PartialOrd for Score {
     fn partial_cmp(&self, other: &Score) -> Option<Ordering> {
-        if match (*self, *other) {
-    		(Score::Running(val1), Score::Running(val2)) => val1 > val2,
-        	(Score::Running(val1), Score::Ended(scr2)) => scr2 < 0i16 || (scr2 == 0i16 && val1 > 0f64),
-        	(Score::Ended(scr1), Score::Running(val2)) => scr1 > 0i16 || (scr1 == 0i16 && val2 < 0f64),
-        	(Score::Ended(scr1), Score::Ended(scr2)) => scr1 > scr2,
-    	} {
-            Some(Ordering::Greater)
-        } else {
-            Some(Ordering::Less)
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -38,53 +55,126 @@ impl Eq for Score {}
 
 impl Ord for Score {
     fn cmp(&self, other: &Score) -> Ordering {
-        if *self == *other {
-            Ordering::Equal
-        } else {
-            self.partial_cmp(other).expect("Should be ordered")
+        self.key().total_cmp(&other.key())
+    }
+}
+
+/// How much time a timed AI can spend thinking.
+pub enum TimeControl {
+    /// The same time for every move.
+    PerMove(Duration),
+    /// A time for the whole game, plus an increment for each move played.
+    Game {
+        remaining: Cell<Duration>,
+        increment: Duration,
+    },
+    /// The time left on a clock of the match, which charges the time spent.
+    Clock(Rc<Clock>),
+}
+
+impl TimeControl {
+    pub fn game(total: Duration, increment: Duration) -> TimeControl {
+        TimeControl::Game {
+            remaining: Cell::new(total),
+            increment,
+        }
+    }
+
+    /// The time to spend on the next move, for a player searching with `config`.
+    pub fn budget(&self, turn: &Turn, config: &SearchConfig) -> Result<Duration> {
+        match *self {
+            TimeControl::PerMove(time) => Ok(time),
+            TimeControl::Game { ref remaining, increment } => {
+                Ok(custom_ai::time_for_move(remaining.get(), increment, empties(turn), config))
+            }
+            TimeControl::Clock(ref clock) => {
+                let side = turn.get_state().ok_or(ReversiError::EndedGame(*turn))?;
+                Ok(custom_ai::time_for_move(clock.get_remaining(side), clock.get_increment(), empties(turn), config))
+            }
+        }
+    }
+
+    /// Charges the time spent on a move.
+    pub fn spend(&self, time: Duration) {
+        if let TimeControl::Game { ref remaining, increment } = *self {
+            let left = remaining.get().checked_sub(time).unwrap_or_else(|| Duration::from_secs(0));
+            remaining.set(left + increment);
         }
     }
 }
 
-pub enum AiPlayer {
+fn empties(turn: &Turn) -> usize {
+    turn.get_bit_board().empties() as usize
+}
+
+pub enum AiKind {
     Weak,
     Medium,
     Strong,
     Custom,
+    /// The custom AI, deepening its search as long as its time allows.
+    Timed(TimeControl),
 }
 
-impl game::IsPlayer<::OtherAction> for AiPlayer {
+pub struct AiPlayer {
+    pub kind: AiKind,
+    /// How the searches of the custom AI are run.
+    pub config: SearchConfig,
+    /// Follows the searches of the custom AI, silent by default.
+    pub observer: Box<dyn SearchObserver>,
+    /// The transposition table of the custom AI, kept from one move to the next.
+    tt: TranspositionTable,
+}
+
+impl IsPlayer<::OtherAction> for AiPlayer {
     /// Calls `find_best_move` with suitable parameters
-    fn make_move(&self, turn: &turn::Turn) -> Result<Action> {
-        Ok(game::PlayerAction::Move(try!(match *self {
-            AiPlayer::Weak => AiPlayer::find_best_move(turn, WEAK),
-            AiPlayer::Medium => AiPlayer::find_best_move(turn, MEDIUM),
-            AiPlayer::Strong => AiPlayer::find_best_move(turn, STRONG),
-            AiPlayer::Custom => custom_ai::find_best_move_custom(turn),
-        })))
+    fn make_move(&self, turn: &Turn) -> Result<Action> {
+        Ok(PlayerAction::Move(match self.kind {
+            AiKind::Weak => self.find_best_move(turn, WEAK),
+            AiKind::Medium => self.find_best_move(turn, MEDIUM),
+            AiKind::Strong => self.find_best_move(turn, STRONG),
+            AiKind::Custom => self.search(turn, SearchLimit::Depth(custom_ai::USUAL_DEPTH)),
+            AiKind::Timed(ref time_control) => {
+                let start = Instant::now();
+                let coord = self.search(turn, SearchLimit::Time(time_control.budget(turn, &self.config)?));
+                time_control.spend(start.elapsed());
+                coord
+            }
+        }?))
     }
 }
 
 impl AiPlayer {
+    pub fn new(kind: AiKind, config: SearchConfig) -> AiPlayer {
+        AiPlayer {
+            kind,
+            tt: config.new_table(),
+            config,
+            observer: Box::new(SilentObserver),
+        }
+    }
+
+    /// The move of the custom AI, searching within `limit` with the table of this player.
+    fn search(&self, turn: &Turn, limit: SearchLimit) -> Result<Coord> {
+        custom_ai::find_best_move_with_table(turn.get_bit_board(), limit, &self.config, &self.tt, &*self.observer)
+            .ok_or(ReversiError::EndedGame(*turn))
+    }
+
     /// Find best moves among the legal ones.
     /// Each possibility is evaluated by a method depending on the value of `self` and confronted with the others.
-    pub fn find_best_move(turn: &turn::Turn, comps: u32) -> Result<board::Coord> {
+    pub fn find_best_move(&self, turn: &Turn, comps: u32) -> Result<Coord> {
 
         // If everything is alright, turn shouldn't be ended
         let side = turn.get_state()
-            .ok_or_else(|| ReversiError::EndedGame(*turn))?;
+            .ok_or(ReversiError::EndedGame(*turn))?;
 
-        // Finds all possible legal moves and records their coordinates
-        let mut moves: Vec<Coord> = Vec::new();
-        for row in 0..board::BOARD_SIZE {
-            for col in 0..board::BOARD_SIZE {
-                let coord = board::Coord::new(row, col);
-                if turn.check_move(coord).is_ok() {
-                    moves.push(coord);
-                }
-            }
+        if let Some(coord) = self.config.book_move(turn.get_bit_board()) {
+            return Ok(coord);
         }
 
+        // Finds all possible legal moves and records their coordinates
+        let moves: Vec<Coord> = turn.get_moves();
+
         match moves.len() {
             0 => unreachable!("Game is not ended!"), // Game can't be ended
             1 => Ok(moves[0]), // If there is only one possible move, there's no point in evaluating it.
@@ -111,11 +201,11 @@ impl AiPlayer {
         }
     }
 
-    fn ai_eval(turn: &turn::Turn, comps: u32) -> Result<Score> {
+    fn ai_eval(turn: &Turn, comps: u32) -> Result<Score> {
         if turn.get_state().is_none() {
             Ok(Score::Ended(turn.get_score_diff()))
         } else {
-            let mut score = try!(AiPlayer::ai_eval_with_leftover(turn, comps)).0;
+            let mut score = AiPlayer::ai_eval_with_leftover(turn, comps)?.0;
             // Add some randomness
             let between = Range::new(-RANDOMNESS, RANDOMNESS);
             let mut rng = thread_rng();
@@ -128,7 +218,7 @@ impl AiPlayer {
         }
     }
 
-    fn ai_eval_with_leftover(turn: &turn::Turn, comps: u32) -> Result<(Score, u32)> {
+    fn ai_eval_with_leftover(turn: &Turn, comps: u32) -> Result<(Score, u32)> {
 
         // If everything is alright, turn shouldn't be ended
         // assert!(!this_turn.is_endgame());
@@ -137,15 +227,7 @@ impl AiPlayer {
         let mut moves: Vec<Coord>;
         let mut turn = *turn;
         loop {
-            moves = Vec::new();
-            for row in 0..board::BOARD_SIZE {
-                for col in 0..board::BOARD_SIZE {
-                    let coord = board::Coord::new(row, col);
-                    if turn.check_move(coord).is_ok() {
-                        moves.push(coord);
-                    }
-                }
-            }
+            moves = turn.get_moves();
             match moves.len() {
                 0 => unreachable!("Endgame should have been detected earlier: here it's a waste of computations!"),
                 1 => {
@@ -185,7 +267,7 @@ impl AiPlayer {
         // assert!(!turn.is_endgame());
 
         let mut scores: Vec<Score> = Vec::new();
-        let mut leftover = comps.checked_sub(moves.len() as u32).unwrap_or(0);
+        let mut leftover = comps.saturating_sub(moves.len() as u32);
 
         while let Some(coord) = moves.pop() {
             let mut turn_after_move = turn;
@@ -193,10 +275,10 @@ impl AiPlayer {
             let turns_left = (moves.len() + 1) as u32;
             scores.push(match turn_after_move.get_state() {
                             None => Score::Ended(turn_after_move.get_score_diff()),
-                            Some(_) if leftover < turns_left => Score::Running(try!(AiPlayer::heavy_eval(&turn_after_move))),
+                            Some(_) if leftover < turns_left => Score::Running(AiPlayer::heavy_eval(&turn_after_move)?),
                             _ => {
                                 let new_comps = leftover / turns_left; // since leftover >= turns_left, then new_comps >= 1
-                                let new_score_leftover = try!(AiPlayer::ai_eval_with_leftover(&turn_after_move, new_comps));
+                                let new_score_leftover = AiPlayer::ai_eval_with_leftover(&turn_after_move, new_comps)?;
                                 leftover += new_score_leftover.1;
                                 leftover -= new_comps; // since leftover >= turns_left, leftover - newcomps >= 0
                                 new_score_leftover.0
@@ -212,7 +294,7 @@ impl AiPlayer {
             leftover))
     }
 
-    pub fn heavy_eval(turn: &turn::Turn) -> Result<f64> {
+    pub fn heavy_eval(turn: &Turn) -> Result<f64> {
         // Weights
         const CORNER_BONUS: u16 = 50;
         const ODD_CORNER_MALUS: u16 = 20;
@@ -259,33 +341,33 @@ impl AiPlayer {
 
         for &(corner, odd, odd_corner, even, even_corner, counter_odd, counter_even) in &sides {
 
-            if let Some(disk) = *turn.get_cell(corner)? {
-                match disk.get_side() {
+            if let Some(side) = turn.get_cell(corner)? {
+                match side {
                     Side::Light => score_light += CORNER_BONUS,
                     Side::Dark => score_dark += CORNER_BONUS,
                 }
             } else {
                 for &(coord_odd, coord_even) in &[(odd, even), (counter_odd, counter_even)] {
-                    if let Some(disk) = *turn.get_cell(coord_odd)? {
-                        match disk.get_side() {
+                    if let Some(side) = turn.get_cell(coord_odd)? {
+                        match side {
                             Side::Light => score_dark += ODD_MALUS,
                             Side::Dark => score_light += ODD_MALUS,
                         }
-                    } else if let Some(disk) = *turn.get_cell(coord_even)? {
-                        match disk.get_side() {
+                    } else if let Some(side) = turn.get_cell(coord_even)? {
+                        match side {
                             Side::Light => score_light += EVEN_BONUS,
                             Side::Dark => score_dark += EVEN_BONUS,
                         }
                     }
                 }
-                if let Some(disk) = *turn.get_cell(odd_corner)? {
-                    match disk.get_side() {
+                if let Some(side) = turn.get_cell(odd_corner)? {
+                    match side {
                         Side::Light => score_dark += ODD_CORNER_MALUS,
                         Side::Dark => score_light += ODD_CORNER_MALUS,
                     }
 
-                } else if let Some(disk) = *turn.get_cell(even_corner)? {
-                    match disk.get_side() {
+                } else if let Some(side) = turn.get_cell(even_corner)? {
+                    match side {
                         Side::Light => score_light += EVEN_CORNER_BONUS,
                         Side::Dark => score_dark += EVEN_CORNER_BONUS,
                     }
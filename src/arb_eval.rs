@@ -10,11 +10,15 @@
 #![cfg_attr(feature="clippy", plugin(clippy))]
 
 extern crate rusthello_lib;
-extern crate reversi;
 
-use rusthello_lib::{custom_ai, bit_board};
+use rusthello_lib::{custom_ai, bit_board, record};
+use rusthello_lib::custom_ai::{Deadline, Evaluator, SearchConfig, SearchContext, SearchInfo, SearchObserver,
+                              Solution, SolveMode};
 use rusthello_lib::bit_board::BitBoard;
-use std::time::{Instant};
+use rusthello_lib::game::Coord;
+use rusthello_lib::ggf::GgfGame;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 fn read_board() -> bit_board::BitBoard {
     let stdin = ::std::io::stdin();
@@ -26,8 +30,8 @@ fn read_board() -> bit_board::BitBoard {
         panic!("config must be of length 64");
     }
     let config_v: Vec<char> = config.chars().collect();
-    for i in 0 .. 64 {
-        match config_v[i] {
+    for (i, &disk) in config_v.iter().take(64).enumerate() {
+        match disk {
             'O' => wh |= 1u64 << i,
             'X' => bl |= 1u64 << i,
             '-' => (),
@@ -38,47 +42,146 @@ fn read_board() -> bit_board::BitBoard {
     stdin.read_line(&mut turn_config).unwrap();
     let turn = turn_config.starts_with("Black")
         || turn_config.starts_with("black");
-    BitBoard(bl, wh, turn)
+    BitBoard::new(bl, wh, turn)
+}
+
+/// Compares every move of the games in a GGF file with the custom ai's choice.
+fn analyse_ggf(path: &str, config: &SearchConfig) {
+    let games = GgfGame::load_all(path).unwrap_or_else(|err| panic!("{}", err));
+    for game in games.iter() {
+        println!("{} - {} ({})", game.black, game.white, game.result);
+        let positions = game.positions().unwrap_or_else(|err| panic!("{}", err));
+        for (n, (&board, mv)) in positions.iter().zip(game.moves.iter()).enumerate() {
+            if let Some(coord) = mv.coord {
+                let best = custom_ai::find_best_move_bit_board(board, config, &Trace)
+                    .expect("There is a legal move");
+                println!("{:>3}. {} {}  best: {}{}",
+                         n + 1,
+                         if board.black_to_move() { "B" } else { "W" },
+                         record::format_coord(coord),
+                         record::format_coord(best),
+                         if best == coord { "" } else { " *" });
+            }
+        }
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+fn format_line(line: &[Coord]) -> String {
+    let line: Vec<String> = line.iter()
+        .map(|&coord| if coord.get_row() < 8 { record::format_coord(coord) } else { "pass".to_string() })
+        .collect();
+    line.join(" ")
+}
+
+/// Prints the best moves of every iteration of the searches.
+struct Trace;
+
+impl SearchObserver for Trace {
+    fn depth_finished(&self, info: &SearchInfo) {
+        if info.solving {
+            println!("evals[{} empties]: #Nodes = {}, NPS = {}k", info.depth, info.nodes, info.nps() / 1000.0);
+        } else {
+            println!("evals[depth = {}]: #Nodes = {}, NPS = {}k", info.depth, info.nodes, info.nps() / 1000.0);
+        }
+        for root_move in info.moves.iter().take(4) {
+            println!("{:?}: {}", root_move.score, format_line(&root_move.line));
+        }
+    }
+}
+
+/// Prints what the endgame solver found.
+fn print_solution(name: &str, solution: &Solution) {
+    let elapsed = seconds(solution.elapsed);
+    println!("{} analysis: {}sec", name, elapsed);
+    println!("Best move = {}, score = {:?}",
+             solution.best_move.map_or("pass".to_string(), record::format_coord), solution.score);
+    println!("PV = {}", format_line(&solution.principal_variation));
+    println!("#Nodes = {}", solution.nodes);
+    println!("NPS = {}k", solution.nodes as f64 / elapsed / 1000.0);
 }
 
 fn main() {
     // Main intro
     println!("Evaluation by custom ai");
+    let mut args = ::std::env::args().skip(1);
+    let mut ggf_path = None;
+    let mut techniques = false;
+    let mut config = SearchConfig::default();
+    while let Some(arg) = args.next() {
+        if arg == "--weights" {
+            let path = args.next().expect("--weights needs a file name");
+            config.evaluator = Arc::new(Evaluator::load(&path).unwrap_or_else(|err| panic!("{}", err)));
+        } else if arg == "--threads" {
+            let threads = args.next().and_then(|threads| threads.parse().ok())
+                .expect("--threads needs a number");
+            config.set_threads(threads);
+        } else if arg == "--techniques" {
+            techniques = true;
+        } else {
+            ggf_path = Some(arg);
+        }
+    }
+    // With a GGF file as argument, analyses its games instead
+    if let Some(path) = ggf_path {
+        analyse_ggf(&path, &config);
+        return;
+    }
     let board = read_board();
-    let BitBoard(bl, wh, turn) = board;
-    let my = if turn { bl } else { wh };
-    let opp = if turn { wh } else { bl };
+    let (my, opp) = (board.mover(), board.opponent());
     println!("{}", bit_board::show_bit_board(board));
     let start = Instant::now();
-    custom_ai::find_best_move_bit_board(board);
-    let end = start.elapsed();
-    let end = end.as_secs() as f64 +
-        end.subsec_nanos() as f64 * 1e-9;
-    println!("Analysis: {}sec", end);
-    // full analysis
-    if bit_board::get_tempo(my, opp) >= 40 {
+    custom_ai::find_best_move_bit_board(board, &config, &Trace);
+    println!("Analysis: {}sec", seconds(start.elapsed()));
+    // midgame analysis, iteratively deepened as by the custom ai
+    if bit_board::get_tempo(my, opp) < 64 - config.endgame_length() as i16 {
         let mut moves_and_scores = Vec::new();
         let moves = bit_board::valid_moves_set(my, opp);
         let start = Instant::now();
         let mut nnodes = 0;
-        custom_ai::ai_eval_till_end(my, opp, moves, &mut moves_and_scores,
-                                    true, &mut nnodes);
-        let end = start.elapsed();
-        let end = end.as_secs() as f64 +
-            end.subsec_nanos() as f64 * 1e-9;
-        println!("Lock analysis: {}sec", end);
-        println!("#Nodes = {}", nnodes);
-        println!("NPS = {}k", nnodes as f64 / end / 1000.0);
-        let start = Instant::now();
-        nnodes = 0;
-        custom_ai::ai_eval_till_end(my, opp, moves, &mut moves_and_scores,
-                                    false, &mut nnodes);
-        let end = start.elapsed();
-        let end = end.as_secs() as f64 +
-            end.subsec_nanos() as f64 * 1e-9;
-        println!("Full analysis: {}sec", end);
+        let tt = config.new_table();
+        let never = Deadline::never();
+        let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &Trace };
+        for depth in 1 .. custom_ai::USUAL_DEPTH + 1 {
+            custom_ai::ai_eval_with_depth(my, opp, depth, moves, &mut moves_and_scores, &ctx, &mut nnodes);
+        }
+        let end = seconds(start.elapsed());
+        println!("Midgame analysis ({} threads): {}sec", config.threads(), end);
         println!("#Nodes = {}", nnodes);
         println!("NPS = {}k", nnodes as f64 / end / 1000.0);
     }
+    // full analysis
+    if bit_board::get_tempo(my, opp) >= 40 {
+        let tt = config.new_table();
+        let never = Deadline::never();
+        let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &Trace };
+        let wld = custom_ai::solve(board, SolveMode::WinLossDraw, &ctx).expect("There is no deadline");
+        print_solution("WLD", &wld);
+        tt.clear();
+        let full = custom_ai::solve(board, SolveMode::Exact, &ctx).expect("There is no deadline");
+        print_solution("Full", &full);
+        // the same without each of the enhancements of the solver
+        if techniques {
+            let all = custom_ai::EndgameOptions::all();
+            let variants = [("parity", custom_ai::EndgameOptions { parity: false, ..all }),
+                            ("last moves", custom_ai::EndgameOptions { last_moves: false, ..all }),
+                            ("stability", custom_ai::EndgameOptions { stability: false, ..all }),
+                            ("ETC", custom_ai::EndgameOptions { etc: false, ..all }),
+                            ("any enhancement", custom_ai::EndgameOptions::none())];
+            for &(name, options) in variants.iter() {
+                let mut config = config.clone();
+                config.endgame = options;
+                tt.clear();
+                let ctx = SearchContext { config: &config, ..ctx };
+                let solution = custom_ai::solve(board, SolveMode::Exact, &ctx).expect("There is no deadline");
+                println!("Full analysis without {}: {}sec", name, seconds(solution.elapsed));
+                println!("#Nodes = {} ({:+.1}%)", solution.nodes,
+                         (solution.nodes as f64 / full.nodes as f64 - 1.0) * 100.0);
+            }
+        }
+    }
     // custom_ai::find_best_move_custom(&turn).unwrap();
 }
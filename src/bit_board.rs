@@ -1,13 +1,235 @@
-/**
+/*!
  * The implementation is done in reference to https://github.com/koba-e964/othello-ai/blob/master/CBoard.hs, which uses routines that are originally in edax.
  */
 
-// is_dark_turn: bool
-#[derive(Clone, Copy)]
-pub struct BitBoard(pub u64, pub u64, pub bool);
+use {Result};
+use game::{BOARD_SIZE, Coord, ReversiError, Side};
+use std::fmt;
+use std::str::FromStr;
+
+/// A position: the disks of each side, and whose turn it is.
+/// Unlike `game::Turn`, passing is explicit: the side to move may have no legal move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitBoard {
+    black: u64,
+    white: u64,
+    black_to_move: bool,
+}
+
+/// The starting position: d5 and e4 are black, d4 and e5 white, black to move.
+pub const START: BitBoard = BitBoard {
+    black: 0x0000000810000000,
+    white: 0x0000001008000000,
+    black_to_move: true,
+};
+
+impl BitBoard {
+    /// Panics if a cell has disks of both sides.
+    pub fn new(black: u64, white: u64, black_to_move: bool) -> BitBoard {
+        assert_eq!(black & white, 0, "A cell has disks of both sides");
+        BitBoard {
+            black,
+            white,
+            black_to_move,
+        }
+    }
+
+    /// The position with `my` to move, as seen by the search.
+    pub fn from_my_opp(my: u64, opp: u64, black_to_move: bool) -> BitBoard {
+        if black_to_move {
+            BitBoard::new(my, opp, true)
+        } else {
+            BitBoard::new(opp, my, false)
+        }
+    }
+
+    pub fn black(&self) -> u64 {
+        self.black
+    }
+
+    pub fn white(&self) -> u64 {
+        self.white
+    }
+
+    pub fn black_to_move(&self) -> bool {
+        self.black_to_move
+    }
+
+    pub fn side_to_move(&self) -> Side {
+        if self.black_to_move { Side::Dark } else { Side::Light }
+    }
+
+    /// The disks of the side to move.
+    pub fn mover(&self) -> u64 {
+        if self.black_to_move { self.black } else { self.white }
+    }
+
+    /// The disks of the other side.
+    pub fn opponent(&self) -> u64 {
+        if self.black_to_move { self.white } else { self.black }
+    }
+
+    pub fn disc_count(&self, side: Side) -> u32 {
+        match side {
+            Side::Dark => self.black.count_ones(),
+            Side::Light => self.white.count_ones(),
+        }
+    }
+
+    pub fn empties(&self) -> u32 {
+        (!(self.black | self.white)).count_ones()
+    }
+
+    /// The side of the disk on a cell, if any.
+    pub fn cell(&self, coord: Coord) -> Result<Option<Side>> {
+        let disk = disk(coord)?;
+        Ok(if self.black & disk != 0 {
+            Some(Side::Dark)
+        } else if self.white & disk != 0 {
+            Some(Side::Light)
+        } else {
+            None
+        })
+    }
+
+    /// The legal moves of the side to move, as a set of cells.
+    pub fn legal_moves(&self) -> u64 {
+        valid_moves_set(self.mover(), self.opponent())
+    }
+
+    /// The legal moves of the side to move, by row then column.
+    pub fn moves(&self) -> Moves {
+        Moves(self.legal_moves())
+    }
+
+    /// Plays a move of the side to move, giving the turn to the other side
+    /// (even if it has to pass).
+    pub fn play(&self, coord: Coord) -> Result<BitBoard> {
+        let disk = disk(coord)?;
+        if self.legal_moves() & disk == 0 {
+            return Err(ReversiError::IllegalMove(coord));
+        }
+        let (my, opp) = move_bit_board(self.mover(), self.opponent(), disk);
+        Ok(BitBoard::from_my_opp(opp, my, !self.black_to_move))
+    }
+
+    /// Gives the turn to the other side.
+    pub fn pass(&self) -> BitBoard {
+        BitBoard { black_to_move: !self.black_to_move, ..*self }
+    }
+
+    /// Whether neither side can move.
+    pub fn is_game_over(&self) -> bool {
+        self.legal_moves() == 0 && valid_moves_set(self.opponent(), self.mover()) == 0
+    }
+}
+
+/// The bitboard with just the cell of `coord`.
+fn disk(coord: Coord) -> Result<u64> {
+    let (row, col) = coord.get_row_col();
+    if row < BOARD_SIZE && col < BOARD_SIZE {
+        Ok(1u64 << (row * BOARD_SIZE + col))
+    } else {
+        Err(ReversiError::OutOfBoundCoord(coord))
+    }
+}
 
-pub fn get_score_diff(light: u64, dark: u64) -> i16 {
-    light.count_ones() as i16 - dark.count_ones() as i16
+/// The iterator over the legal moves of a position, see `BitBoard::moves`.
+#[derive(Debug, Clone)]
+pub struct Moves(u64);
+
+impl Iterator for Moves {
+    type Item = Coord;
+
+    fn next(&mut self) -> Option<Coord> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(Coord::new(square / BOARD_SIZE, square % BOARD_SIZE))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Moves {}
+
+/// On one line: the 64 cells from a1 to h8, row by row (`X` black, `O` white, `-` empty),
+/// a space and the side to move.
+impl fmt::Display for BitBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for square in 0 .. 64 {
+            let mask = 1u64 << square;
+            write!(f, "{}", if self.black & mask != 0 {
+                'X'
+            } else if self.white & mask != 0 {
+                'O'
+            } else {
+                '-'
+            })?;
+        }
+        write!(f, " {}", if self.black_to_move { 'X' } else { 'O' })
+    }
+}
+
+/// Parses the format of `Display`. Black disks may also be written `*` or `x`,
+/// white ones `o` and empty cells `.`; spaces between cells are ignored.
+impl FromStr for BitBoard {
+    type Err = String;
+
+    fn from_str(s: &str) -> ::std::result::Result<BitBoard, String> {
+        let mut chars: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+        if chars.len() != 65 {
+            return Err(format!("expected 64 cells and the side to move, found {} characters", chars.len()));
+        }
+        let black_to_move = match chars.pop() {
+            Some('X') | Some('x') | Some('*') => true,
+            Some('O') | Some('o') => false,
+            Some(c) => return Err(format!("invalid side to move '{}'", c)),
+            None => unreachable!(),
+        };
+        let (mut black, mut white) = (0u64, 0u64);
+        for (square, &c) in chars.iter().enumerate() {
+            match c {
+                'X' | 'x' | '*' => black |= 1 << square,
+                'O' | 'o' => white |= 1 << square,
+                '-' | '.' => (),
+                _ => return Err(format!("invalid cell '{}'", c)),
+            }
+        }
+        Ok(BitBoard::new(black, white, black_to_move))
+    }
+}
+
+impl From<::game::Turn> for BitBoard {
+    fn from(turn: ::game::Turn) -> BitBoard {
+        turn.get_bit_board()
+    }
+}
+
+/// Only this direction: a `reversi` turn cannot be built from an arbitrary position.
+#[cfg(feature = "reversi")]
+impl<'a> From<&'a ::reversi::turn::Turn> for BitBoard {
+    fn from(turn: &'a ::reversi::turn::Turn) -> BitBoard {
+        let (mut black, mut white) = (0u64, 0u64);
+        for row in 0 .. BOARD_SIZE {
+            for col in 0 .. BOARD_SIZE {
+                let cell = turn.get_cell(::reversi::board::Coord::new(row, col))
+                    .expect("The coordinates are on the board");
+                match cell.as_ref().map(|disk| disk.get_side()) {
+                    Some(::reversi::Side::Dark) => black |= 1 << (row * BOARD_SIZE + col),
+                    Some(::reversi::Side::Light) => white |= 1 << (row * BOARD_SIZE + col),
+                    None => (),
+                }
+            }
+        }
+        // Once the game has ended, the side to move does not matter
+        BitBoard::new(black, white, turn.get_state() != Some(::reversi::Side::Light))
+    }
 }
 
 pub fn get_tempo(light: u64, dark: u64) -> i16 {
@@ -81,7 +303,20 @@ fn trans_op(trans: Transfer, x: u64) -> u64 {
 }
 
 /// disk must be a singleton
-fn flippable_indices_set(my: u64, opp: u64, dist: u64) -> u64 {
+#[cfg(feature = "kogge-stone")]
+fn flippable_indices_set(my: u64, opp: u64, disk: u64) -> u64 {
+    flips_kogge_stone(my, opp, disk)
+}
+
+/// disk must be a singleton
+#[cfg(not(feature = "kogge-stone"))]
+fn flippable_indices_set(my: u64, opp: u64, disk: u64) -> u64 {
+    flips_reference(my, opp, disk)
+}
+
+/// The disks flipped by playing `disk`, walking each direction disk by disk.
+/// Kept as a reference for `flips_kogge_stone`.
+pub fn flips_reference(my: u64, opp: u64, dist: u64) -> u64 {
     let mut cur = 0;
     for &trans in TRANSFERS.iter() {
         cur |= flippable_indices_in_dir(trans, my, opp, dist);
@@ -109,21 +344,92 @@ fn flippable_indices_in_dir(trans: Transfer, my: u64, opp: u64, disk: u64)
     }
 }
     
-pub const BLACK: usize = 0;
-pub const WHITE: usize = 1;
+/// The disks flipped by playing `disk`, without branching on the length of the lines:
+/// in each direction, the run of opponent's disks next to `disk` is flooded with
+/// shifts of 1, 2 and 4 cells (Kogge-Stone), then kept if one of my disks closes it.
+pub fn flips_kogge_stone(my: u64, opp: u64, disk: u64) -> u64 {
+    // Opponent's disks on the edge columns cannot be flipped horizontally or diagonally
+    let inner = opp & 0x7e7e7e7e7e7e7e7e;
+    flips_left(my, opp, disk, 8, 0xffffffffffffffff) | flips_right(my, opp, disk, 8, 0xffffffffffffffff) |
+    flips_left(my, inner, disk, 1, 0xfefefefefefefefe) | flips_right(my, inner, disk, 1, 0x7f7f7f7f7f7f7f7f) |
+    flips_left(my, inner, disk, 7, 0x7f7f7f7f7f7f7f7f) | flips_right(my, inner, disk, 7, 0xfefefefefefefefe) |
+    flips_left(my, inner, disk, 9, 0xfefefefefefefefe) | flips_right(my, inner, disk, 9, 0x7f7f7f7f7f7f7f7f)
+}
 
-pub fn count_disks(cb: BitBoard, color: usize) -> i16 {
-    let BitBoard(bl, wh, _turn) = cb;
-    (if color == BLACK {
-        bl.count_ones()
+/// Flips towards higher squares; `mask` is where a shift by `dir` cannot have wrapped around.
+fn flips_left(my: u64, opp: u64, disk: u64, dir: usize, mask: u64) -> u64 {
+    let mut run = disk;
+    let mut pro = opp;
+    run |= pro & (run << dir);
+    pro &= pro << dir;
+    run |= pro & (run << (2 * dir));
+    pro &= pro << (2 * dir);
+    run |= pro & (run << (4 * dir));
+    if (run << dir) & mask & my != 0 {
+        run & !disk
     } else {
-        wh.count_ones()
-    }) as i16
-}    
+        0
+    }
+}
 
+/// Flips towards lower squares; `mask` is where a shift by `dir` cannot have wrapped around.
+fn flips_right(my: u64, opp: u64, disk: u64, dir: usize, mask: u64) -> u64 {
+    let mut run = disk;
+    let mut pro = opp;
+    run |= pro & (run >> dir);
+    pro &= pro >> dir;
+    run |= pro & (run >> (2 * dir));
+    pro &= pro >> (2 * dir);
+    run |= pro & (run >> (4 * dir));
+    if (run >> dir) & mask & my != 0 {
+        run & !disk
+    } else {
+        0
+    }
+}
 
+/// The four lines through a cell, as `(dir, left mask, right mask)`: a shift by `dir`
+/// towards higher (resp. lower) squares cannot have wrapped around within the mask.
+const LINES: [(usize, u64, u64); 4] = [(1, 0xfefefefefefefefe, 0x7f7f7f7f7f7f7f7f),
+                                       (7, 0x7f7f7f7f7f7f7f7f, 0xfefefefefefefefe),
+                                       (8, 0xffffffffffffffff, 0xffffffffffffffff),
+                                       (9, 0xfefefefefefefefe, 0x7f7f7f7f7f7f7f7f)];
+
+/// The cells next to those of `cells` along a line, on either side.
+fn line_neighbours(cells: u64, (dir, left, right): (usize, u64, u64)) -> u64 {
+    ((cells << dir) & left) | ((cells >> dir) & right)
+}
+
+/// Disks of `my` that can never be flipped (not necessarily all of them): along each
+/// of its four lines, a stable disk is on the edge of the board, on a full line,
+/// or next to another stable disk of `my`.
+pub fn stable_disks(my: u64, opp: u64) -> u64 {
+    let empty = !(my | opp);
+    let mut safe = [0; 4];
+    for (safe, &line) in safe.iter_mut().zip(LINES.iter()) {
+        let edge = !(((!0 << line.0) & line.1) & ((!0 >> line.0) & line.2));
+        let mut open = empty;
+        for _ in 0 .. 7 {
+            open |= line_neighbours(open, line);
+        }
+        *safe = edge | !open;
+    }
+    let mut stable = 0;
+    loop {
+        let mut next = my;
+        for (&safe, &line) in safe.iter().zip(LINES.iter()) {
+            next &= safe | line_neighbours(stable, line);
+        }
+        if next == stable {
+            return stable;
+        }
+        stable = next;
+    }
+}
+
+/// The board drawn on several lines, with the side to move.
 pub fn show_bit_board(board: BitBoard) -> String {
-    let BitBoard(bl, wh, turn) = board;
+    let (bl, wh, turn) = (board.black, board.white, board.black_to_move);
     let mut ret = 
     (" |A B C D E F G H \n-+----------------\n").to_string();
     for row in 1 .. 9 {
@@ -146,3 +452,101 @@ pub fn show_bit_board(board: BitBoard) -> String {
     ret = format!("{} {} to move", ret, if turn { "Black" } else { "White" });
     ret
 }
+
+/// The 8 symmetries of the board. Rotations are clockwise, with row 1 on top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Swaps row 1 and row 8.
+    FlipVertical,
+    /// Swaps column A and column H.
+    FlipHorizontal,
+    /// Mirrors along the A1-H8 diagonal.
+    FlipDiagonal,
+    /// Mirrors along the H1-A8 diagonal.
+    FlipAntiDiagonal,
+}
+
+/// All the symmetries, the rotations first.
+pub const SYMMETRIES: [Symmetry; 8] = [Symmetry::Identity,
+                                       Symmetry::Rotate90,
+                                       Symmetry::Rotate180,
+                                       Symmetry::Rotate270,
+                                       Symmetry::FlipVertical,
+                                       Symmetry::FlipHorizontal,
+                                       Symmetry::FlipDiagonal,
+                                       Symmetry::FlipAntiDiagonal];
+
+impl Symmetry {
+    /// Moves the cells of a bitboard.
+    pub fn apply(self, board: u64) -> u64 {
+        match self {
+            Symmetry::Identity => board,
+            Symmetry::Rotate90 => flip_horizontal(flip_diagonal(board)),
+            Symmetry::Rotate180 => flip_horizontal(flip_vertical(board)),
+            Symmetry::Rotate270 => flip_vertical(flip_diagonal(board)),
+            Symmetry::FlipVertical => flip_vertical(board),
+            Symmetry::FlipHorizontal => flip_horizontal(board),
+            Symmetry::FlipDiagonal => flip_diagonal(board),
+            Symmetry::FlipAntiDiagonal => flip_anti_diagonal(board),
+        }
+    }
+
+    pub fn apply_coord(self, coord: Coord) -> Coord {
+        let square = self.apply(1u64 << (coord.get_row() * 8 + coord.get_col())).trailing_zeros() as usize;
+        Coord::new(square / 8, square % 8)
+    }
+
+    /// Moves both sides' disks, keeping the side to move.
+    pub fn apply_board(self, board: BitBoard) -> BitBoard {
+        BitBoard { black: self.apply(board.black), white: self.apply(board.white), ..board }
+    }
+
+    /// The symmetry undoing this one.
+    pub fn inverse(self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            other => other,
+        }
+    }
+}
+
+/// Swaps row 1 and row 8.
+pub fn flip_vertical(board: u64) -> u64 {
+    board.swap_bytes()
+}
+
+/// Swaps column A and column H.
+pub fn flip_horizontal(mut board: u64) -> u64 {
+    board = ((board >> 1) & 0x5555555555555555) | ((board & 0x5555555555555555) << 1);
+    board = ((board >> 2) & 0x3333333333333333) | ((board & 0x3333333333333333) << 2);
+    ((board >> 4) & 0x0f0f0f0f0f0f0f0f) | ((board & 0x0f0f0f0f0f0f0f0f) << 4)
+}
+
+/// Mirrors along the A1-H8 diagonal, swapping rows and columns.
+pub fn flip_diagonal(mut board: u64) -> u64 {
+    let mut t = 0x0f0f0f0f00000000 & (board ^ (board << 28));
+    board ^= t ^ (t >> 28);
+    t = 0x3333000033330000 & (board ^ (board << 14));
+    board ^= t ^ (t >> 14);
+    t = 0x5500550055005500 & (board ^ (board << 7));
+    board ^ t ^ (t >> 7)
+}
+
+/// Mirrors along the H1-A8 diagonal.
+pub fn flip_anti_diagonal(board: u64) -> u64 {
+    flip_vertical(flip_horizontal(flip_diagonal(board)))
+}
+
+/// The symmetric version of a position with the smallest bitboards (`my` first),
+/// and the symmetry leading to it.
+pub fn canonical(my: u64, opp: u64) -> (u64, u64, Symmetry) {
+    SYMMETRIES.iter()
+        .map(|&symmetry| (symmetry.apply(my), symmetry.apply(opp), symmetry))
+        .min()
+        .expect("There are 8 symmetries")
+}
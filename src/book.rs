@@ -0,0 +1,334 @@
+//! An opening book: known positions with statistics about the moves played in them.
+//!
+//! Positions are stored from the side to move, and only once for all their
+//! symmetric versions (the one given by `bit_board::canonical`).
+//!
+//! Books are saved as a binary file: the magic bytes `RTBK`, the version and the number
+//! of positions (as little-endian `u32`), then for each position the disks of the side
+//! to move and of the opponent (as little-endian `u64`), the number of moves (a byte),
+//! and for each move its square, its visits (`u32`) and its value (`f32`).
+
+use game::Coord;
+use bit_board;
+use bit_board::BitBoard;
+use rand::thread_rng;
+use rand::distributions::{IndependentSample, Range};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+const MAGIC: &[u8; 4] = b"RTBK";
+const VERSION: u32 = 1;
+
+/// How the book chooses among the moves it knows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BookMode {
+    /// Always the same move: the best one, the first square on ties.
+    Deterministic,
+    /// A random move, the more visited the more likely.
+    WeightedRandom,
+    /// A random move among those tied for the best value, so that equally good
+    /// lines all get played.
+    RandomBest,
+}
+
+impl FromStr for BookMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BookMode, String> {
+        match s {
+            "deterministic" => Ok(BookMode::Deterministic),
+            "weighted" => Ok(BookMode::WeightedRandom),
+            "best" => Ok(BookMode::RandomBest),
+            _ => Err(format!("unknown book mode {} (deterministic, weighted or best)", s)),
+        }
+    }
+}
+
+/// A move of a book position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookMove {
+    /// How many times the move was played or searched.
+    pub visits: u32,
+    /// Its value, as the disk difference expected for the side playing it.
+    pub value: f32,
+}
+
+pub struct Book {
+    /// The moves of each (canonical) position, by square.
+    positions: HashMap<(u64, u64), Vec<(u8, BookMove)>>,
+    /// The book is not used after that many moves.
+    pub max_depth: usize,
+    pub mode: BookMode,
+}
+
+impl Book {
+    pub fn new() -> Book {
+        Book {
+            positions: HashMap::new(),
+            max_depth: 60,
+            mode: BookMode::WeightedRandom,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The known moves of a position, as disks in the position's own orientation.
+    pub fn moves(&self, my: u64, opp: u64) -> Vec<(u64, BookMove)> {
+        let (cmy, copp, symmetry) = bit_board::canonical(my, opp);
+        self.positions.get(&(cmy, copp)).map_or(Vec::new(), |moves| {
+            moves.iter()
+                .map(|&(square, mv)| (symmetry.inverse().apply(1u64 << square), mv))
+                .collect()
+        })
+    }
+
+    /// Sets the known moves of a position, given as disks in the position's own orientation.
+    pub fn set_moves(&mut self, my: u64, opp: u64, moves: &[(u64, BookMove)]) {
+        let (cmy, copp, symmetry) = bit_board::canonical(my, opp);
+        let mut moves: Vec<(u8, BookMove)> = moves.iter()
+            .map(|&(disk, mv)| (symmetry.apply(disk).trailing_zeros() as u8, mv))
+            .collect();
+        moves.sort_by_key(|&(square, _)| square);
+        self.positions.insert((cmy, copp), moves);
+    }
+
+    /// The book move for a position, if any.
+    pub fn choose(&self, board: BitBoard) -> Option<Coord> {
+        let (my, opp) = (board.mover(), board.opponent());
+        if (bit_board::get_tempo(my, opp) as usize).saturating_sub(4) >= self.max_depth {
+            return None;
+        }
+        let legal = bit_board::valid_moves_set(my, opp);
+        let moves: Vec<(u64, BookMove)> = self.moves(my, opp)
+            .into_iter()
+            .filter(|&(disk, _)| disk & legal != 0)
+            .collect();
+        let best = moves.iter().map(|&(_, mv)| mv.value).fold(f32::NEG_INFINITY, f32::max);
+        let disk = match self.mode {
+            BookMode::Deterministic => {
+                moves.iter().filter(|&&(_, mv)| mv.value == best).map(|&(disk, _)| disk).min()
+            }
+            BookMode::RandomBest => {
+                let best_moves: Vec<u64> = moves.iter()
+                    .filter(|&&(_, mv)| mv.value == best)
+                    .map(|&(disk, _)| disk)
+                    .collect();
+                if best_moves.is_empty() {
+                    None
+                } else {
+                    Some(best_moves[Range::new(0, best_moves.len()).ind_sample(&mut thread_rng())])
+                }
+            }
+            BookMode::WeightedRandom => {
+                let total: u64 = moves.iter().map(|&(_, mv)| mv.visits as u64).sum();
+                if total == 0 {
+                    None
+                } else {
+                    let mut pick = Range::new(0, total).ind_sample(&mut thread_rng());
+                    moves.iter()
+                        .find(|&&(_, mv)| {
+                            if pick < mv.visits as u64 {
+                                true
+                            } else {
+                                pick -= mv.visits as u64;
+                                false
+                            }
+                        })
+                        .map(|&(disk, _)| disk)
+                }
+            }
+        };
+        disk.map(|disk| {
+            let square = disk.trailing_zeros() as usize;
+            Coord::new(square / 8, square % 8)
+        })
+    }
+
+    /// The negamax value of a position for the side to move, as far as the book knows it.
+    pub fn value(&self, my: u64, opp: u64) -> Option<f32> {
+        if bit_board::valid_moves_set(my, opp) == 0 {
+            return if bit_board::valid_moves_set(opp, my) == 0 {
+                Some(my.count_ones() as f32 - opp.count_ones() as f32)
+            } else {
+                self.value(opp, my).map(|val| -val)
+            };
+        }
+        self.moves(my, opp).into_iter().map(|(_, mv)| mv.value).fold(None, |best, val| {
+            Some(best.map_or(val, |best: f32| best.max(val)))
+        })
+    }
+
+    /// Adds a line to the book: it follows the least explored of the moves up to `window`
+    /// disks worse than the best one, down to a position the book does not know yet, which
+    /// is added with its moves as given by `evaluate`, and the values are backed up along
+    /// the line. No position is added after `plies` moves.
+    /// Returns the moves of the line and whether a position was added.
+    pub fn add_line<F>(&mut self, plies: usize, window: f32, mut evaluate: F) -> (Vec<Coord>, bool)
+        where F: FnMut(u64, u64) -> Vec<(u64, BookMove)>
+    {
+        let (mut my, mut opp) = (bit_board::START.mover(), bit_board::START.opponent());
+        // The positions of the line, with the moves played in them
+        let mut path = Vec::new();
+        let mut added = false;
+        loop {
+            if bit_board::valid_moves_set(my, opp) == 0 {
+                if bit_board::valid_moves_set(opp, my) == 0 {
+                    break;
+                }
+                ::std::mem::swap(&mut my, &mut opp);
+                continue;
+            }
+            let known = self.moves(my, opp);
+            if known.is_empty() {
+                let evaluated = evaluate(my, opp);
+                self.set_moves(my, opp, &evaluated);
+                added = true;
+                break;
+            }
+            if (bit_board::get_tempo(my, opp) as usize).saturating_sub(4) >= plies {
+                break;
+            }
+            let best = known.iter().map(|&(_, mv)| mv.value).fold(f32::NEG_INFINITY, f32::max);
+            let &(disk, _) = known.iter()
+                .filter(|&&(_, mv)| mv.value >= best - window)
+                .min_by(|&&(_, a), &&(_, b)| {
+                    a.visits.cmp(&b.visits).then(b.value.partial_cmp(&a.value).expect("Values are not NaN"))
+                })
+                .expect("The best move is close enough to itself");
+            path.push((my, opp, disk));
+            let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+            my = nopp;
+            opp = nmy;
+        }
+
+        // Backs the values up, from the end of the line
+        for &(my, opp, disk) in path.iter().rev() {
+            let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+            let child = self.value(nopp, nmy);
+            let moves: Vec<(u64, BookMove)> = self.moves(my, opp)
+                .into_iter()
+                .map(|(other, mut mv)| {
+                    if other == disk {
+                        mv.visits += 1;
+                        if let Some(val) = child {
+                            mv.value = -val;
+                        }
+                    }
+                    (other, mv)
+                })
+                .collect();
+            self.set_moves(my, opp, &moves);
+        }
+        let line = path.iter()
+            .map(|&(_, _, disk)| {
+                let square = disk.trailing_zeros() as usize;
+                Coord::new(square / 8, square % 8)
+            })
+            .collect();
+        (line, added)
+    }
+
+    pub fn load(path: &str) -> Result<Book, String> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|err| format!("cannot read {}: {}", path, err))?;
+        let invalid = || format!("{} is not a valid book", path);
+        if bytes.len() < 12 || &bytes[0 .. 4] != MAGIC || read_u32(&bytes[4 ..]) != VERSION {
+            return Err(invalid());
+        }
+        let mut book = Book::new();
+        let mut pos = 12;
+        for _ in 0 .. read_u32(&bytes[8 ..]) {
+            if bytes.len() < pos + 17 {
+                return Err(invalid());
+            }
+            let my = read_u64(&bytes[pos ..]);
+            let opp = read_u64(&bytes[pos + 8 ..]);
+            let count = bytes[pos + 16] as usize;
+            pos += 17;
+            if bytes.len() < pos + 9 * count {
+                return Err(invalid());
+            }
+            // Only the legal moves of the position are known
+            let legal = bit_board::valid_moves_set(my, opp);
+            let mut moves = Vec::with_capacity(count);
+            for mv in bytes[pos .. pos + 9 * count].chunks(9) {
+                if mv[0] >= 64 || legal & 1 << mv[0] == 0 {
+                    return Err(invalid());
+                }
+                moves.push((mv[0], BookMove {
+                    visits: read_u32(&mv[1 ..]),
+                    value: f32::from_bits(read_u32(&mv[5 ..])),
+                }));
+            }
+            pos += 9 * count;
+            book.positions.insert((my, opp), moves);
+        }
+        Ok(book)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        write_u32(&mut bytes, VERSION);
+        write_u32(&mut bytes, self.positions.len() as u32);
+        // Sorted, so that the same book is always saved the same way
+        let mut positions: Vec<_> = self.positions.iter().collect();
+        positions.sort_by_key(|&(&key, _)| key);
+        for (&(my, opp), moves) in positions {
+            write_u64(&mut bytes, my);
+            write_u64(&mut bytes, opp);
+            bytes.push(moves.len() as u8);
+            for &(square, mv) in moves.iter() {
+                bytes.push(square);
+                write_u32(&mut bytes, mv.visits);
+                write_u32(&mut bytes, mv.value.to_bits());
+            }
+        }
+        File::create(path)
+            .and_then(|mut file| file.write_all(&bytes))
+            .map_err(|err| format!("cannot write {}: {}", path, err))
+    }
+}
+
+impl Default for Book {
+    fn default() -> Book {
+        Book::new()
+    }
+}
+
+/// Loads the book in `path`, to be used for the first `max_depth` moves.
+pub fn load_book(path: &str, max_depth: usize, mode: BookMode) -> Result<Book, String> {
+    let mut book = Book::load(path)?;
+    book.max_depth = max_depth;
+    book.mode = mode;
+    Ok(book)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    read_u32(bytes) as u64 | (read_u32(&bytes[4 ..]) as u64) << 32
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    for shift in [0, 8, 16, 24].iter() {
+        bytes.push((value >> shift) as u8);
+    }
+}
+
+fn write_u64(bytes: &mut Vec<u8>, value: u64) {
+    write_u32(bytes, value as u32);
+    write_u32(bytes, (value >> 32) as u32);
+}
@@ -0,0 +1,151 @@
+//! Grows an opening book from the searches of the custom AI.
+
+#![crate_name = "bookgen"]
+#![crate_type = "bin"]
+#![cfg_attr(feature="clippy", feature(plugin))]
+#![cfg_attr(feature="clippy", plugin(clippy))]
+
+extern crate rusthello_lib;
+
+use rusthello_lib::{bit_board, custom_ai, record};
+use rusthello_lib::ai_player::Score;
+use rusthello_lib::book::{Book, BookMove};
+use rusthello_lib::custom_ai::{Deadline, Evaluator, SearchConfig, SearchContext, SilentObserver};
+use std::env;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: bookgen [options]
+
+Grows the opening book in FILE (created if missing), one line at a time: each line follows
+the most promising moves of the book down to a position it does not know yet, which is added
+with all its moves evaluated by a search, and the values are backed up along the line.
+
+Options:
+    --book FILE      the book to grow (default book.bin)
+    --lines N        number of lines to add (default 100)
+    --depth D        search depth of the evaluations (default 8)
+    --plies N        do not add positions after N moves (default 12)
+    --window W       also follow moves up to W disks worse than the best (default 2)
+    --weights FILE   evaluate positions with the pattern weights in FILE
+    --save-every N   save the book after every N lines (default 10)";
+
+struct Options {
+    book: String,
+    lines: usize,
+    depth: usize,
+    plies: usize,
+    window: f32,
+    save_every: usize,
+    /// How the positions are searched.
+    config: SearchConfig,
+}
+
+fn parse_number<T: ::std::str::FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|value| value.parse::<T>().ok())
+        .ok_or_else(|| format!("{} needs a number", arg))
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        book: "book.bin".to_string(),
+        lines: 100,
+        depth: 8,
+        plies: 12,
+        window: 2.0,
+        save_every: 10,
+        config: SearchConfig::default(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--book" => options.book = args.next().ok_or_else(|| "--book needs a file name".to_string())?,
+            "--lines" => options.lines = parse_number(&arg, args.next())?,
+            "--depth" => options.depth = parse_number(&arg, args.next())?,
+            "--plies" => options.plies = parse_number(&arg, args.next())?,
+            "--window" => options.window = parse_number(&arg, args.next())?,
+            "--save-every" => options.save_every = parse_number(&arg, args.next())?,
+            "--weights" => {
+                let path = args.next().ok_or_else(|| "--weights needs a file name".to_string())?;
+                options.config.evaluator = Arc::new(Evaluator::load(&path)?);
+            }
+            "-h" | "--help" => return Err(String::new()),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+    if options.depth == 0 || options.save_every == 0 {
+        return Err("--depth and --save-every must be positive".to_string());
+    }
+    Ok(options)
+}
+
+/// Evaluates each move of a position on its own, for the side to move.
+fn evaluate(my: u64, opp: u64, depth: usize, ctx: &SearchContext) -> Vec<(u64, BookMove)> {
+    let mut nnodes = 0;
+    let mut moves = bit_board::valid_moves_set(my, opp);
+    let mut evaluated = Vec::new();
+    while moves != 0 {
+        let disk = 1u64 << moves.trailing_zeros();
+        moves ^= disk;
+        let mut moves_and_scores = Vec::new();
+        custom_ai::ai_eval_with_depth(my, opp, depth, disk, &mut moves_and_scores, ctx, &mut nnodes);
+        // Scores are from the opponent's point of view
+        let value = match moves_and_scores[0].1 {
+            Score::Running(val) => -val as f32,
+            Score::Ended(diff) => -diff as f32,
+        };
+        evaluated.push((disk, BookMove { visits: 0, value }));
+    }
+    evaluated
+}
+
+fn save(book: &Book, path: &str) {
+    if let Err(err) = book.save(path) {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("Error: {}\n", err);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    let mut book = if Path::new(&options.book).exists() {
+        Book::load(&options.book).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        })
+    } else {
+        Book::new()
+    };
+    println!("{} positions in {}", book.len(), options.book);
+
+    let tt = options.config.new_table();
+    let never = Deadline::never();
+    let ctx = SearchContext { config: &options.config, tt: &tt, deadline: &never, observer: &SilentObserver };
+    for n in 0 .. options.lines {
+        let (line, added) = book.add_line(options.plies, options.window, |my, opp| {
+            evaluate(my, opp, options.depth, &ctx)
+        });
+        let (my, opp) = (bit_board::START.mover(), bit_board::START.opponent());
+        let line_text: Vec<String> = line.iter().map(|&coord| record::format_coord(coord)).collect();
+        println!("Line {:>4}: {:<36} start value {:+.2}{}",
+                 n + 1,
+                 line_text.concat(),
+                 book.value(my, opp).unwrap_or(0.0),
+                 if added { "" } else { " (nothing new)" });
+        if (n + 1) % options.save_every == 0 {
+            save(&book, &options.book);
+        }
+    }
+    save(&book, &options.book);
+    println!("{} positions saved to {}", book.len(), options.book);
+}
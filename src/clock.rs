@@ -0,0 +1,61 @@
+//! A chess clock keeping the time left to each side of a match.
+
+use game::Side;
+use std::cell::Cell;
+use std::time::Duration;
+
+pub struct Clock {
+    increment: Duration,
+    dark: Cell<Duration>,
+    light: Cell<Duration>,
+}
+
+impl Clock {
+    /// Both sides start with `initial` time and earn `increment` for every move they play.
+    pub fn new(initial: Duration, increment: Duration) -> Clock {
+        Clock {
+            increment,
+            dark: Cell::new(initial),
+            light: Cell::new(initial),
+        }
+    }
+
+    pub fn get_increment(&self) -> Duration {
+        self.increment
+    }
+
+    pub fn get_remaining(&self, side: Side) -> Duration {
+        self.side_time(side).get()
+    }
+
+    /// Charges `time` to `side`'s clock, adding the increment if a move was played.
+    /// Returns `false` if `side` ran out of time.
+    /// The time is charged once the player has answered: a player who runs out
+    /// while thinking only loses on time when answering.
+    pub fn charge(&self, side: Side, time: Duration, moved: bool) -> bool {
+        let remaining = self.side_time(side);
+        match remaining.get().checked_sub(time) {
+            Some(left) if left > Duration::from_secs(0) => {
+                remaining.set(if moved { left + self.increment } else { left });
+                true
+            }
+            _ => {
+                remaining.set(Duration::from_secs(0));
+                false
+            }
+        }
+    }
+
+    fn side_time(&self, side: Side) -> &Cell<Duration> {
+        match side {
+            Side::Dark => &self.dark,
+            Side::Light => &self.light,
+        }
+    }
+}
+
+/// Formats a time as minutes and seconds, e.g. `4:07`.
+pub fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
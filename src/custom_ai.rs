@@ -3,96 +3,451 @@ use rand::thread_rng;
 use rand::distributions::{IndependentSample, Range};
 use ai_player::{Score};
 
-use reversi::{board, turn, Side, ReversiError};
-use reversi::board::{Coord};
+use game::{Coord, ReversiError, Turn};
 use bit_board;
 use bit_board::BitBoard;
+use book::Book;
+use transposition::{Bound, Entry, TranspositionTable, SOLVED};
+use pattern_eval::PatternWeights;
 
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use rayon;
+use rayon::prelude::*;
 use smallvec::SmallVec;
 
 type SVec<T> = SmallVec<[T; 16]>;
+/// A move of the endgame solver with the position after it, as `(key, disk, opp, my)`.
+type Child<K> = (K, u64, u64, u64);
 
 const RANDOMNESS: f64 = 0.05f64;
 
-const USUAL_DEPTH: usize = 5;
-const ENDGAME_LENGTH: usize = 19;
+pub const USUAL_DEPTH: usize = 9;
+/// With this many empty cells or less, the game is searched till the end,
+/// given enough threads (see `endgame_length`).
+pub const ENDGAME_LENGTH: usize = 22;
+/// The same, on a single thread.
+const SERIAL_ENDGAME_LENGTH: usize = 19;
+/// Depth searched before the endgame solver starts, when the solver may run out of time.
+const FALLBACK_DEPTH: usize = 4;
+/// The clock is read only once every that many polls of a `Deadline`.
+const POLLS_PER_CHECK: usize = 1 << 10;
+/// Default size of the transposition tables, in megabytes.
+const TT_SIZE_MB: usize = 16;
+/// The endgame solver does not use the transposition table with this many empty cells or less.
+const TT_MIN_EMPTIES: i16 = 6;
+/// The parallel endgame solver leaves positions with less empty cells to the serial one.
+const PARALLEL_MIN_EMPTIES: i16 = 14;
+/// The endgame solver leaves positions with this many empty cells or less to `solve_last`.
+const LAST_MOVES: i16 = 4;
+/// The quadrants of the board, the regions of the parity ordering.
+const QUADRANTS: [u64; 4] = [0x000000000f0f0f0f, 0x00000000f0f0f0f0, 0x0f0f0f0f00000000, 0xf0f0f0f000000000];
+/// Lazy SMP helpers search one ply deeper than the main thread once every that many helpers.
+const HELPER_DEPTH_PERIOD: usize = 2;
 
-fn coord_to_string(c: Coord) -> String {
-    let (r, c) = c.get_row_col();
-    if r >= 8 || c >= 8 {
-        "Pass".to_string()
-    } else {
-        format!("{}{}", char::from((0x61 + c) as u8), r + 1)
+/// Bounds of the search window, worse (resp. better) than any actual score.
+const MIN_SCORE: Score = Score::Ended(-128);
+const MAX_SCORE: Score = Score::Ended(128);
+/// Disks per point of the classic evaluation, as fitted on self-play games.
+const CLASSIC_DISKS: f64 = 0.125;
+/// Width of the null windows of the principal variation search.
+const NULL_WINDOW: f64 = 1e-6;
+
+/// The evaluation of positions at the leaves of the midgame search.
+#[derive(Default)]
+pub enum Evaluator {
+    /// Mobility and edges, with hand-picked weights.
+    #[default]
+    Classic,
+    /// Patterns, with weights learned from games.
+    Pattern(PatternWeights),
+}
+
+impl Evaluator {
+    /// Pattern weights loaded from a file.
+    pub fn load(path: &str) -> ::std::result::Result<Evaluator, String> {
+        Ok(Evaluator::Pattern(PatternWeights::load(path)?))
+    }
+
+    /// The value of a position for the side to move.
+    pub fn eval(&self, my: u64, opp: u64) -> f64 {
+        match *self {
+            Evaluator::Classic => my_board_eval(my, opp),
+            Evaluator::Pattern(ref weights) => weights.eval(my, opp),
+        }
+    }
+
+    /// Converts a value given by `eval` into the disk difference it predicts.
+    pub fn disks(&self, value: f64) -> f64 {
+        match *self {
+            Evaluator::Classic => value * CLASSIC_DISKS,
+            // Pattern weights are fitted to disk differences
+            Evaluator::Pattern(_) => value,
+        }
+    }
+
+    /// Converts a score of the searches into the disk difference it predicts.
+    pub fn score_disks(&self, score: Score) -> f64 {
+        match score {
+            Score::Running(val) => self.disks(val),
+            Score::Ended(diff) => diff as f64,
+        }
     }
 }
 
-fn line_to_string(line: &[Coord]) -> String {
-    if line.len() == 0 {
-        "".to_string()
-    } else {
-        let mut ret = " -".to_string();
-        for &mv in line.iter() {
-            ret = format!("{} {}", ret, coord_to_string(mv));
-        }
-        ret
-    }
-}
-
-pub fn find_best_move_custom(turn: &turn::Turn) -> Result<board::Coord> {
-    let mut bl = 0;
-    let mut wh = 0;
-    for row in 0 .. 8 {
-        for col in 0 .. 8 {
-            let idx = row * 8 + col;
-            let cell = turn.get_cell(Coord::new(row, col))?;
-            match *cell {
-                Some(disk) => match disk.get_side() {
-                    Side::Dark => bl |= 1 << idx,
-                    Side::Light => wh |= 1 << idx,
-                },
-                None => (),
+/// How the searches of a player are run. Each player has its own, so that players
+/// of the same process can be set up differently.
+#[derive(Clone, Default)]
+pub struct SearchConfig {
+    /// The number of search threads, 0 for the default.
+    threads: usize,
+    /// The threads the searches run on, those of the calling thread if None.
+    pool: Option<Arc<rayon::ThreadPool>>,
+    /// The evaluation at the leaves of the midgame search.
+    pub evaluator: Arc<Evaluator>,
+    /// The opening book consulted before searching, if any.
+    pub book: Option<Arc<Book>>,
+    /// The enhancements of the endgame solver in use.
+    pub endgame: EndgameOptions,
+    /// Size of the transposition tables of the searches, in megabytes, 0 for the default.
+    pub tt_size_mb: usize,
+    /// Whether the midgame scores of the root moves get noise, so that the moves within
+    /// the noise of the best one are picked at random. Off by default.
+    pub noise: bool,
+}
+
+impl SearchConfig {
+    /// Sets the number of threads each search runs on, 0 restoring the default.
+    /// A single thread means the calling thread only, so that games played in parallel
+    /// do not compete for the search threads.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.pool = if threads > 1 {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build()
+                .expect("The search threads can be spawned");
+            Some(Arc::new(pool))
+        } else {
+            None
+        };
+        self.threads = threads;
+    }
+
+    /// The number of threads each search runs on, by default those of the calling thread's pool.
+    pub fn threads(&self) -> usize {
+        match self.threads {
+            0 => rayon::current_num_threads(),
+            threads => threads,
+        }
+    }
+
+    /// With this many empty cells or less, the game is searched till the end.
+    /// Each doubling of the threads solves about one more empty cell in the same time.
+    pub fn endgame_length(&self) -> usize {
+        let doublings = 63 - (self.threads() as u64).leading_zeros() as usize;
+        min(SERIAL_ENDGAME_LENGTH + doublings, ENDGAME_LENGTH)
+    }
+
+    /// The move of the book for `board`, if there is a book and it knows the position.
+    pub fn book_move(&self, board: BitBoard) -> Option<Coord> {
+        self.book.as_ref().and_then(|book| book.choose(board))
+    }
+
+    /// A new transposition table of the configured size.
+    pub fn new_table(&self) -> TranspositionTable {
+        TranspositionTable::new(match self.tt_size_mb {
+            0 => TT_SIZE_MB,
+            size => size,
+        })
+    }
+
+    /// Runs `op` on the search threads.
+    fn install<R: Send, F: FnOnce() -> R + Send>(&self, op: F) -> R {
+        match self.pool {
+            Some(ref pool) => pool.install(op),
+            None => op(),
+        }
+    }
+}
+
+/// What all the nodes of a search share.
+#[derive(Clone, Copy)]
+pub struct SearchContext<'a> {
+    pub config: &'a SearchConfig,
+    pub tt: &'a TranspositionTable,
+    pub deadline: &'a Deadline<'a>,
+    /// Follows the search, `&SilentObserver` if nothing has to.
+    pub observer: &'a dyn SearchObserver,
+}
+
+/// The enhancements of the endgame solver. Each of them can be switched off,
+/// to measure what it saves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndgameOptions {
+    /// Among moves leaving the opponent as many replies, the moves in a quadrant
+    /// with an odd number of empty cells first.
+    pub parity: bool,
+    /// Solve the last `LAST_MOVES` empty cells with a dedicated routine.
+    pub last_moves: bool,
+    /// Fail low when the stable disks of the opponent leave no score above alpha.
+    pub stability: bool,
+    /// Enhanced transposition cutoff: fail high when the table already proves a move good enough.
+    pub etc: bool,
+}
+
+impl EndgameOptions {
+    pub fn all() -> EndgameOptions {
+        EndgameOptions {
+            parity: true,
+            last_moves: true,
+            stability: true,
+            etc: true,
+        }
+    }
+
+    pub fn none() -> EndgameOptions {
+        EndgameOptions {
+            parity: false,
+            last_moves: false,
+            stability: false,
+            etc: false,
+        }
+    }
+}
+
+impl Default for EndgameOptions {
+    fn default() -> EndgameOptions {
+        EndgameOptions::all()
+    }
+}
+
+/// A move at the root of a search, with its score for the side to move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootMove {
+    pub coord: Coord,
+    pub score: Score,
+    /// The expected line, from the move on. Passes show as `Coord::new(8, 8)`.
+    pub line: Vec<Coord>,
+}
+
+/// What an iteration of a search found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchInfo {
+    /// The depth of the iteration, or the number of empty cells if it goes till the end of the game.
+    pub depth: usize,
+    /// Whether the iteration goes till the end of the game.
+    pub solving: bool,
+    /// The moves searched, best first. In a midgame search, the scores of the moves
+    /// after the first one may only be upper bounds.
+    pub moves: Vec<RootMove>,
+    /// The nodes of the iteration, on all the search threads.
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> Option<&RootMove> {
+        self.moves.first()
+    }
+
+    /// Nodes per second, 0 if no time could be measured.
+    pub fn nps(&self) -> f64 {
+        let secs = self.elapsed.as_secs() as f64 + self.elapsed.subsec_nanos() as f64 * 1e-9;
+        if secs > 0.0 { self.nodes as f64 / secs } else { 0.0 }
+    }
+}
+
+/// Follows the searches of the custom AI, which call it from their main thread only.
+/// Every method does nothing unless overridden.
+pub trait SearchObserver: Send + Sync {
+    /// An iteration starts, as described by `SearchInfo::depth` and `SearchInfo::solving`.
+    fn depth_started(&self, _depth: usize, _solving: bool) {}
+    /// The iteration found a move better than those searched before it.
+    fn new_best_move(&self, _depth: usize, _solving: bool, _best: &RootMove) {}
+    /// The iteration is over. Not called if the deadline expires first.
+    fn depth_finished(&self, _info: &SearchInfo) {}
+}
+
+/// The default observer, ignoring everything.
+pub struct SilentObserver;
+
+impl SearchObserver for SilentObserver {}
+
+/// How long the search is allowed to go on.
+#[derive(Debug, Clone, Copy)]
+pub enum SearchLimit {
+    /// Deepen iteratively up to the given depth (or solve the endgame).
+    Depth(usize),
+    /// Deepen iteratively until the given time is over.
+    Time(Duration),
+}
+
+/// The moment a search has to stop at. Searches poll it and, once expired,
+/// unwind without storing or returning anything from the aborted iteration.
+/// The parallel endgame solver gives each split point a child deadline,
+/// which also expires when the split point no longer needs its moves searched.
+pub struct Deadline<'a> {
+    at: Option<Instant>,
+    polls: AtomicUsize,
+    expired: AtomicBool,
+    parent: Option<&'a Deadline<'a>>,
+}
+
+impl<'a> Deadline<'a> {
+    pub fn never() -> Deadline<'static> {
+        Deadline {
+            at: None,
+            polls: AtomicUsize::new(0),
+            expired: AtomicBool::new(false),
+            parent: None,
+        }
+    }
+
+    pub fn after(time: Duration) -> Deadline<'static> {
+        Deadline {
+            at: Some(Instant::now() + time),
+            polls: AtomicUsize::new(0),
+            expired: AtomicBool::new(false),
+            parent: None,
+        }
+    }
+
+    /// A deadline expiring with this one, or earlier with `expire`.
+    pub fn child<'b>(&'b self) -> Deadline<'b> {
+        Deadline {
+            at: self.at,
+            polls: AtomicUsize::new(0),
+            expired: AtomicBool::new(false),
+            parent: Some(self),
+        }
+    }
+
+    /// Polls the deadline, reading the clock every now and then.
+    pub fn check(&self) -> bool {
+        let polls = self.polls.load(Ordering::Relaxed) + 1;
+        if polls >= POLLS_PER_CHECK {
+            self.polls.store(0, Ordering::Relaxed);
+            if self.at.is_some_and(|at| Instant::now() >= at) {
+                // The time is up for the whole search
+                let mut deadline = Some(self);
+                while let Some(expired) = deadline {
+                    expired.expire();
+                    deadline = expired.parent;
+                }
             }
+        } else {
+            self.polls.store(polls, Ordering::Relaxed);
         }
+        self.has_expired()
+    }
+
+    /// Whether the deadline, or one it is a child of, was found expired.
+    pub fn has_expired(&self) -> bool {
+        self.expired.load(Ordering::Relaxed) || self.parent.is_some_and(|parent| parent.has_expired())
+    }
+
+    /// Makes the deadline, and its children, expire now.
+    pub fn expire(&self) {
+        self.expired.store(true, Ordering::Relaxed);
     }
-    let is_black = turn.get_state() == Some(Side::Dark);
-    match find_best_move_bit_board(BitBoard(bl, wh, is_black)) {
+
+    /// Time left before the deadline, if any.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.at.map(|at| {
+            let now = Instant::now();
+            if now < at { at - now } else { Duration::from_secs(0) }
+        })
+    }
+}
+
+/// Splits the `remaining` time of a player between the moves still to be played,
+/// `empties` being the number of empty cells on the board.
+pub fn time_for_move(remaining: Duration, increment: Duration, empties: usize, config: &SearchConfig) -> Duration {
+    // The last moves are solved exactly and take almost no time.
+    let moves_to_go = max(empties.saturating_sub(config.endgame_length() / 2) / 2, 1) as u32;
+    min(remaining / moves_to_go + increment, remaining / 2)
+}
+
+pub fn find_best_move_custom(turn: &Turn,
+                             limit: SearchLimit,
+                             config: &SearchConfig,
+                             observer: &dyn SearchObserver)
+                             -> Result<Coord> {
+    let tt = config.new_table();
+    match find_best_move_with_table(turn.get_bit_board(), limit, config, &tt, observer) {
         Some(v) => Ok(v),
         None => Err(ReversiError::EndedGame(*turn)),
     }
 }
-pub fn find_best_move_bit_board(BitBoard(bl, wh, turn): BitBoard)
-                                           -> Option<board::Coord> {
+
+pub fn find_best_move_bit_board(board: BitBoard, config: &SearchConfig, observer: &dyn SearchObserver)
+                                -> Option<Coord> {
+    let tt = config.new_table();
+    find_best_move_with_table(board, SearchLimit::Depth(USUAL_DEPTH), config, &tt, observer)
+}
+
+/// Like `find_best_move_bit_board`, but searches within the given limit and with
+/// the given transposition table, which can be kept between moves: the entries
+/// of the former searches are kept, but make way for the new ones.
+/// With a time limit, the result of the last completed iteration is returned.
+/// The iterations are reported to `observer`.
+pub fn find_best_move_with_table(board: BitBoard,
+                                 limit: SearchLimit,
+                                 config: &SearchConfig,
+                                 tt: &TranspositionTable,
+                                 observer: &dyn SearchObserver)
+                                 -> Option<Coord> {
     // Finds all possible legal moves and records their coordinates
-    let my = if turn { bl } else { wh };
-    let opp = if turn { wh } else { bl };
-    let moves = bit_board::valid_moves_set(my, opp);
-    let opp_moves = bit_board::valid_moves_set(opp, my);
-    if moves == 0 && opp_moves == 0 {
-        // Game has ended.
+    let (my, opp) = (board.mover(), board.opponent());
+    let moves = board.legal_moves();
+    if board.is_game_over() {
         return None;
     }
-    
+    if let Some(coord) = config.book_move(board) {
+        return Some(coord);
+    }
+    tt.new_search();
+
     match moves.count_ones() {
-        0 => return None,
+        0 => None,
         _num_moves => {
             let tempo = bit_board::get_tempo(my, opp);
             let left = (64 - tempo) as usize;
             let mut moves_and_scores = Vec::new();
-            if left > ENDGAME_LENGTH {
+            let (max_depth, deadline) = match limit {
+                SearchLimit::Depth(depth) => (depth, Deadline::never()),
+                // Deeper than this, the search would reach the end of the game anyway
+                SearchLimit::Time(time) => (left, Deadline::after(time)),
+            };
+            // The first iteration is always completed, so that there is a move to return
+            let never = Deadline::never();
+            let first = SearchContext { config, tt, deadline: &never, observer };
+            let ctx = SearchContext { deadline: &deadline, ..first };
+            let mut nnodes = 0;
+            if left > config.endgame_length() {
                 // use iterative deepening
                 let mut depth = 1;
-                while depth <= USUAL_DEPTH {
-                    ai_eval_with_depth(my, opp, depth, moves,
-                                       &mut moves_and_scores);
+                while depth <= max_depth {
+                    let ctx = if depth == 1 { &first } else { &ctx };
+                    if !ai_eval_with_depth(my, opp, depth, moves,
+                                           &mut moves_and_scores, ctx, &mut nnodes) {
+                        break;
+                    }
                     depth += 1;
                 }
             } else {
-                let mut nnodes = 0;
+                if deadline.remaining().is_some() {
+                    // A quick search to fall back on if the solver runs out of time
+                    for depth in 1 .. min(FALLBACK_DEPTH, left) + 1 {
+                        let ctx = if depth == 1 { &first } else { &ctx };
+                        ai_eval_with_depth(my, opp, depth, moves,
+                                           &mut moves_and_scores, ctx, &mut nnodes);
+                    }
+                }
                 ai_eval_till_end(my, opp, moves,
-                                 &mut moves_and_scores, true, &mut nnodes);
+                                 &mut moves_and_scores, true, &ctx, &mut nnodes);
             }
             let best_move_and_score =
                 moves_and_scores.into_iter().min_by_key(|&(_, score)| score)
@@ -102,87 +457,394 @@ pub fn find_best_move_bit_board(BitBoard(bl, wh, turn): BitBoard)
     }
 }
 
+/// The line expected from `board` on, following the best moves stored in `tt`
+/// by a previous search. Passes show as `Coord::new(8, 8)`.
+pub fn principal_variation(board: BitBoard, tt: &TranspositionTable) -> Vec<Coord> {
+    table_line(board.mover(), board.opponent(), 64, tt)
+}
+
+/// Like `principal_variation`, but stopping after `plies` moves, passes aside.
+fn table_line(mut my: u64, mut opp: u64, plies: usize, tt: &TranspositionTable) -> Vec<Coord> {
+    let mut line = Vec::new();
+    let mut played = 0;
+    while played < plies {
+        let moves = bit_board::valid_moves_set(my, opp);
+        if moves == 0 {
+            if bit_board::valid_moves_set(opp, my) == 0 {
+                break;
+            }
+            line.push(disk_to_coord(0));
+            ::std::mem::swap(&mut my, &mut opp);
+            continue;
+        }
+        let disk = match tt.probe(my, opp) {
+            Some(entry) if entry.best_move & moves != 0 => entry.best_move,
+            _ => break,
+        };
+        line.push(disk_to_coord(disk));
+        played += 1;
+        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+        my = nopp;
+        opp = nmy;
+    }
+    line
+}
+
+/*
+ * If moves_and_scores is not empty, its order (as left by the previous iteration)
+ * is used to order the root moves.
+ * Only the score of the best move is exact: the others may be upper bounds (lower bounds,
+ * from the opponent's point of view), unless config.noise is on and they are within its reach.
+ * Returns false, leaving moves_and_scores untouched, if the deadline expires.
+ * With several search threads, helper threads run the same search (Lazy SMP), some of
+ * them one ply deeper, and fill the shared transposition table for the main one.
+ * nnodes counts the nodes of all the threads.
+ */
 pub fn ai_eval_with_depth(my: u64, opp: u64, depth: usize, moves: u64,
-                      moves_and_scores: &mut Vec<(Coord, Score)>) {
-    let mut moves_scores_lines = SVec::new();
-    moves_and_scores.clear();
+                      moves_and_scores: &mut Vec<(Coord, Score)>,
+                      ctx: &SearchContext,
+                      nnodes: &mut u64) -> bool {
+    let (start, start_nodes) = (Instant::now(), *nnodes);
+    ctx.observer.depth_started(depth, false);
+    // Moves of the previous iteration come first, best first
+    let mut ordered: SVec<u64> = moves_and_scores.iter()
+        .map(|&(coord, _)| coord_to_disk(coord))
+        .filter(|&disk| moves & disk != 0)
+        .collect();
     let mut restmoves = moves;
+    for &disk in ordered.iter() {
+        restmoves &= !disk;
+    }
     while restmoves != 0 {
         let disk = 1u64 << restmoves.trailing_zeros();
-        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
         restmoves ^= disk;
-        let (score, line) =
-            ai_eval_iddfs(nopp, nmy, depth);
-        moves_scores_lines.push((disk_to_coord(disk), score, line));
-    }
-    moves_scores_lines.sort_unstable_by_key(|&(_, score, _)| score);
-    eprintln!("evals[depth = {}]:", depth);
-    for i in 0 .. ::std::cmp::min(4, moves_scores_lines.len()) {
-        let (mv, score, line) = moves_scores_lines[i].clone();
-        eprintln!("{:?}: {}{}", negate_score(score), coord_to_string(mv),
-                  line_to_string(&line));
+        ordered.push(disk);
     }
+    let helper_nodes = AtomicU64::new(0);
+    let searched = ctx.config.install(|| {
+        let stop = ctx.deadline.child();
+        let ctx = SearchContext { deadline: &stop, ..*ctx };
+        // Only the main search is followed
+        let helper_ctx = SearchContext { observer: &SilentObserver, ..ctx };
+        let mut searched = None;
+        rayon::scope(|scope| {
+            for helper in 1 .. ctx.config.threads() {
+                let (ordered, ctx, helper_nodes) = (&ordered, &helper_ctx, &helper_nodes);
+                scope.spawn(move |_| {
+                    let mut nodes = 0;
+                    ai_eval_root(my, opp, depth + helper % HELPER_DEPTH_PERIOD, ordered, ctx, &mut nodes);
+                    helper_nodes.fetch_add(nodes, Ordering::Relaxed);
+                });
+            }
+            searched = ai_eval_root(my, opp, depth, &ordered, &ctx, nnodes);
+            // The helpers are of no use once the main search is done
+            stop.expire();
+        });
+        searched
+    });
+    *nnodes += helper_nodes.into_inner();
+    let mut moves_scores_lines = match searched {
+        Some(moves_scores_lines) => moves_scores_lines,
+        None => return false,
+    };
+    moves_scores_lines.sort_by_key(|&(_, score, _)| score);
+    ctx.observer.depth_finished(&SearchInfo {
+        depth,
+        solving: false,
+        moves: moves_scores_lines.iter()
+            .map(|&(mv, score, ref line)| root_move(mv, negate_score(score), line))
+            .collect(),
+        nodes: *nnodes - start_nodes,
+        elapsed: start.elapsed(),
+    });
     *moves_and_scores = moves_scores_lines.into_iter()
         .map(|(mv, score, _)| (mv, score)).collect();
+    true
 }
 
-fn ai_eval_iddfs(my: u64, opp: u64, depth: usize)
-                 -> (Score, SVec<Coord>) {
-    let (mut score, mut line) = ai_eval_iddfs_internal(my, opp, depth);
-    // Add some randomness
+/// The root of the midgame search, on the calling thread: the moves in the given order
+/// with their scores (from the opponent's point of view) and lines, or None if the deadline expires.
+/// New best moves are reported to the observer of `ctx`.
+fn ai_eval_root(my: u64, opp: u64, depth: usize, ordered: &[u64],
+                ctx: &SearchContext,
+                nnodes: &mut u64)
+                -> Option<SVec<(Coord, Score, SVec<Coord>)>> {
+    let mut moves_scores_lines = SVec::new();
+    // Principal variation search: the first move gets the full window,
+    // the others are only tested against the best score so far, or the lowest one
+    // that the noise can make the best.
+    let mut alpha = MIN_SCORE;
+    for (i, &disk) in ordered.iter().enumerate() {
+        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+        let floor = if ctx.config.noise { noise_floor(alpha) } else { alpha };
+        let (mut score, mut line) = if i == 0 {
+            ai_eval_iddfs(nopp, nmy, depth, negate_score(MAX_SCORE), negate_score(alpha), ctx, nnodes)
+        } else {
+            ai_eval_iddfs(nopp, nmy, depth, negate_score(next_score(floor)), negate_score(floor), ctx, nnodes)
+        };
+        if i > 0 && negate_score(score) > floor {
+            let (new_score, new_line) =
+                ai_eval_iddfs(nopp, nmy, depth, negate_score(MAX_SCORE), negate_score(floor), ctx, nnodes);
+            score = new_score;
+            line = new_line;
+        }
+        if ctx.deadline.has_expired() {
+            return None;
+        }
+        if i == 0 || negate_score(score) > alpha {
+            ctx.observer.new_best_move(depth, false, &root_move(disk_to_coord(disk), negate_score(score), &line));
+        }
+        // The noise is only fair between exact scores
+        let exact = i == 0 || negate_score(score) > floor;
+        alpha = max(alpha, negate_score(score));
+        if ctx.config.noise && exact {
+            score = add_randomness(score);
+        }
+        moves_scores_lines.push((disk_to_coord(disk), score, line));
+    }
+    Some(moves_scores_lines)
+}
+
+/// A root move, for observers, with its score for the side to move and the line after it.
+fn root_move(coord: Coord, score: Score, line: &[Coord]) -> RootMove {
+    let mut moves = Vec::with_capacity(line.len() + 1);
+    moves.push(coord);
+    moves.extend_from_slice(line);
+    RootMove {
+        coord,
+        score,
+        line: moves,
+    }
+}
+
+/// The score under which a move cannot beat one scoring `best` once both get noise.
+fn noise_floor(best: Score) -> Score {
+    match best {
+        Score::Running(val) => Score::Running(val - 3.0 * RANDOMNESS * val.abs()),
+        _ => best,
+    }
+}
+
+fn add_randomness(score: Score) -> Score {
     let between = Range::new(-RANDOMNESS, RANDOMNESS);
     let mut rng = thread_rng();
-    score = match score {
+    match score {
         Score::Running(val) => Score::Running(val * (1.0 + between.ind_sample(&mut rng))),
         _ => score,
-    };
-    // Done, return
+    }
+}
+
+fn ai_eval_iddfs(my: u64, opp: u64, depth: usize, alpha: Score, beta: Score,
+                 ctx: &SearchContext, nnodes: &mut u64)
+                 -> (Score, SVec<Coord>) {
+    let (score, mut line) = ai_eval_iddfs_internal(my, opp, depth, alpha, beta, ctx, nnodes);
     line.reverse(); // the last move is pushed first
     (score, line)
 }
 
-fn ai_eval_iddfs_internal(my: u64, opp: u64, depth: usize)
+/// Fail-soft alpha-beta (PVS) search: a score `<= alpha` is an upper bound,
+/// a score `>= beta` is a lower bound, and anything in between is exact.
+/// The result is meaningless if the deadline has expired.
+fn ai_eval_iddfs_internal(my: u64, opp: u64, depth: usize,
+                          alpha: Score, beta: Score,
+                          ctx: &SearchContext,
+                          nnodes: &mut u64)
                           -> (Score, SVec<Coord>) {
+    *nnodes += 1;
+    if ctx.deadline.check() {
+        return (MIN_SCORE, SVec::new());
+    }
     let mut moves = bit_board::valid_moves_set(my, opp);
     if moves == 0 && bit_board::valid_moves_set(opp, my) == 0 {
         return (Score::Ended(get_score_diff(my, opp)), SVec::new());
     }
     if depth == 0 {
         return
-            (Score::Running(my_board_eval(my, opp)), SVec::new());
+            (Score::Running(ctx.config.evaluator.eval(my, opp)), SVec::new());
+    }
+    let mut tt_move = 0;
+    if let Some(entry) = ctx.tt.probe(my, opp) {
+        tt_move = entry.best_move;
+        if entry.depth as usize >= depth {
+            match entry.bound {
+                Bound::Exact => {
+                    // The line goes on as far as the table knows it, the last move first
+                    let line = table_line(my, opp, depth, ctx.tt).into_iter().rev().collect();
+                    return (entry.score, line);
+                }
+                Bound::Lower if entry.score >= beta => return (entry.score, SVec::new()),
+                Bound::Upper if entry.score <= alpha => return (entry.score, SVec::new()),
+                _ => (),
+            }
+        }
     }
 
     if moves == 0 {
-        let (score, mut line) = ai_eval_iddfs_internal(opp, my, depth);
+        let (score, mut line) =
+            ai_eval_iddfs_internal(opp, my, depth, negate_score(beta), negate_score(alpha), ctx, nnodes);
         line.push(Coord::new(8, 8)); // Pass
         return (negate_score(score), line);
     }
     // If everything is alright, turn shouldn't be ended
     // assert!(!turn.is_endgame());
-    
-    let mut scores: SVec<(SVec<Coord>, Score)> = SVec::new();
-    
+
+    // The move of the previous iteration (from the transposition table) goes first,
+    // then the moves leaving the opponent with less mobility.
+    let mut children: SVec<((bool, u32), u64, u64, u64)> = SVec::new();
     while moves != 0 {
         let disk = 1u64 << moves.trailing_zeros();
         moves ^= disk;
         let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
-        let (new_score, mut line)
-            = ai_eval_iddfs_internal(nopp, nmy, depth - 1);
-        line.push(disk_to_coord(disk));
-        scores.push((line, new_score));
+        let opp_mobility = if depth > 1 {
+            bit_board::valid_moves_set(nopp, nmy).count_ones()
+        } else {
+            0
+        };
+        children.push(((disk != tt_move, opp_mobility), disk, nmy, nopp));
     }
-    
-    let (line, score) = scores.into_iter().min_by_key(|&(_, score)| score).expect("Why should this fail?");
-    (negate_score(score), line)
+    children.sort_unstable_by_key(|&(key, _, _, _)| key);
+
+    let mut best = MIN_SCORE;
+    let mut best_move = 0;
+    let mut line = SVec::new();
+    let original_alpha = alpha;
+    let mut alpha = alpha;
+    for (i, &(_, disk, nmy, nopp)) in children.iter().enumerate() {
+        let (mut new_score, mut new_line) = if i == 0 {
+            ai_eval_iddfs_internal(nopp, nmy, depth - 1, negate_score(beta), negate_score(alpha), ctx, nnodes)
+        } else {
+            ai_eval_iddfs_internal(nopp, nmy, depth - 1,
+                                   negate_score(next_score(alpha)), negate_score(alpha), ctx, nnodes)
+        };
+        if i > 0 && negate_score(new_score) > alpha && negate_score(new_score) < beta {
+            let (score, research_line) =
+                ai_eval_iddfs_internal(nopp, nmy, depth - 1, negate_score(beta), negate_score(alpha), ctx, nnodes);
+            new_score = score;
+            new_line = research_line;
+        }
+        if ctx.deadline.has_expired() {
+            return (MIN_SCORE, SVec::new());
+        }
+        let score = negate_score(new_score);
+        if i == 0 || score > best {
+            best = score;
+            best_move = disk;
+            new_line.push(disk_to_coord(disk));
+            line = new_line;
+        }
+        alpha = max(alpha, best);
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    let bound = if best >= beta {
+        Bound::Lower
+    } else if best <= original_alpha {
+        Bound::Upper
+    } else {
+        Bound::Exact
+    };
+    ctx.tt.store(my, opp, depth as u8, bound, best, best_move);
+    (best, line)
+}
+
+/// What the endgame solver has to prove.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolveMode {
+    /// Whether the side to move wins, loses or draws: a positive score is only
+    /// a lower bound and a negative one an upper bound.
+    WinLossDraw,
+    /// The exact final disk difference.
+    Exact,
+    /// The exact score if it lies strictly between the two bounds, else a bound.
+    Window(i16, i16),
+}
+
+impl SolveMode {
+    /// The window of the search: scores outside of it are only bounds.
+    fn window(&self) -> (i16, i16) {
+        match *self {
+            SolveMode::WinLossDraw => (-1, 1),
+            SolveMode::Exact => (-65, 65),
+            SolveMode::Window(alpha, beta) => (alpha, beta),
+        }
+    }
+}
+
+/// The final disk difference for the side to move, as far as the solver proved it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolveScore {
+    Exact(i16),
+    AtLeast(i16),
+    AtMost(i16),
+}
+
+/// The result of `solve`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub mode: SolveMode,
+    /// None if the side to move has to pass, or the game is over.
+    /// With an upper bound, all the moves are as bad and the first one tried is given.
+    pub best_move: Option<Coord>,
+    pub score: SolveScore,
+    /// The expected line, from the best move on, as far as it is known.
+    /// Passes show as `Coord::new(8, 8)`.
+    pub principal_variation: Vec<Coord>,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+/// Solves a position till the end of the game, printing nothing.
+/// Returns None if the deadline expires first.
+pub fn solve(board: BitBoard, mode: SolveMode, ctx: &SearchContext) -> Option<Solution> {
+    let start = Instant::now();
+    let (my, opp) = (board.mover(), board.opponent());
+    let (alpha, beta) = mode.window();
+    let nodes = AtomicU64::new(0);
+    let (score, line, _) = ctx.config.install(|| {
+        ai_eval_till_end_parallel(my, opp, alpha, beta, false, ctx, &nodes)
+    });
+    if ctx.deadline.has_expired() {
+        return None;
+    }
+    let score = if score >= beta {
+        SolveScore::AtLeast(score)
+    } else if score <= alpha {
+        SolveScore::AtMost(score)
+    } else {
+        SolveScore::Exact(score)
+    };
+    // Bounds come without a line, but the table may still know the move
+    let mut line: Vec<u64> = line.into_iter().rev().collect();
+    let moves = board.legal_moves();
+    if line.is_empty() && moves != 0 {
+        let tt_move = ctx.tt.probe(my, opp).map_or(0, |entry| entry.best_move);
+        line.push(ordered_moves(my, opp, moves, tt_move, false)[0].1);
+    }
+    Some(Solution {
+        mode,
+        best_move: line.first().cloned().filter(|&disk| disk != 0).map(disk_to_coord),
+        score,
+        principal_variation: line.into_iter().map(disk_to_coord).collect(),
+        nodes: nodes.into_inner(),
+        elapsed: start.elapsed(),
+    })
 }
 
 /*
  * If moves_and_scores is not empty, it is a hint to the evaluator.
+ * Returns false, leaving moves_and_scores untouched, if the deadline expires.
+ * Once the first move is searched, the others are searched in parallel.
  */
 pub fn ai_eval_till_end(my: u64, opp: u64, moves: u64,
                         moves_and_scores: &mut Vec<(Coord, Score)>,
                         pruning: bool,
-                        nnodes: &mut u64) {
+                        ctx: &SearchContext,
+                        nnodes: &mut u64) -> bool {
+    let start = Instant::now();
+    let empties = (64 - bit_board::get_tempo(my, opp)) as usize;
+    ctx.observer.depth_started(empties, true);
     let mut moves_scores_lines = SVec::new();
     let mut disks = SVec::new();
     let mut hint = HashMap::<u64, i32>::new();
@@ -193,7 +855,7 @@ pub fn ai_eval_till_end(my: u64, opp: u64, moves: u64,
             hint.insert(disk, diff as i32);
         }
     }
-    moves_and_scores.clear();
+    let tt_move = ctx.tt.probe(my, opp).map_or(0, |entry| entry.best_move);
     let mut moves = moves;
     while moves != 0 {
         let disk = 1u64 << moves.trailing_zeros();
@@ -203,58 +865,251 @@ pub fn ai_eval_till_end(my: u64, opp: u64, moves: u64,
         let mut eigen_score = opp_moves.count_ones() as i32;
         if let Some(&evaluation) = hint.get(&disk) {
             eigen_score = (evaluation << 16) - 1;
+        } else if disk == tt_move {
+            eigen_score = i32::MIN;
         }
         disks.push((eigen_score, disk, nopp, nmy));
     }
     disks.sort_unstable_by_key(|&(sc, _, _, _)| sc);
-    let mut ma = -1i16 << 10;
-    for (_, disk, nopp, nmy) in disks {
-        let mut nnodes_this = 0;
-        let (score, mut line, defunct) =
-            ai_eval_till_end_internal(nopp, nmy, -1 << 10, -ma, pruning,
-                                      &mut nnodes_this);
-        *nnodes += nnodes_this;
-        println!("Move: {} #nodes = {}", coord_to_string(disk_to_coord(disk)),
-                 nnodes_this);
-        ma = max(ma, -score);
-        if !defunct {
-            line.reverse();
-            moves_scores_lines.push((disk_to_coord(disk), score, line));
+    let nodes = AtomicU64::new(0);
+    let searched = ctx.config.install(|| {
+        let mut searched = Vec::new();
+        if let Some((&(_, disk, nopp, nmy), young)) = disks.split_first() {
+            let (score, line, defunct) =
+                ai_eval_till_end_parallel(nopp, nmy, -1 << 10, 1 << 10, pruning, ctx, &nodes);
+            let first = Searched { disk, score, line, defunct };
+            if !(defunct || ctx.deadline.has_expired()) {
+                ctx.observer.new_best_move(empties, true, &solved_move(&first));
+            }
+            searched.push(first);
+            // In lock mode, a winning move is enough
+            if !(ctx.deadline.has_expired() || pruning && score < 0) {
+                let young = young_brothers(young, -score, 1 << 10, pruning, ctx, &nodes);
+                // The brothers are searched together, so only the best of them is reported
+                let best = young.iter().filter(|child| !child.defunct && child.score < score)
+                    .min_by_key(|child| child.score);
+                if let (Some(best), false) = (best, ctx.deadline.has_expired()) {
+                    ctx.observer.new_best_move(empties, true, &solved_move(best));
+                }
+                searched.extend(young);
+            }
         }
-        if pruning && score < 0 {
-            break;
+        searched
+    });
+    if ctx.deadline.has_expired() {
+        return false;
+    }
+    let nodes = nodes.into_inner();
+    *nnodes += nodes;
+    let mut moves: Vec<_> = searched.iter().filter(|child| !child.defunct).map(solved_move).collect();
+    moves.sort_by_key(|root_move| ::std::cmp::Reverse(root_move.score));
+    ctx.observer.depth_finished(&SearchInfo {
+        depth: empties,
+        solving: true,
+        moves,
+        nodes,
+        elapsed: start.elapsed(),
+    });
+    for child in searched {
+        if !child.defunct {
+            let mut line = child.line;
+            line.reverse();
+            moves_scores_lines.push((disk_to_coord(child.disk), child.score, line));
         }
     }
     moves_scores_lines.sort_unstable_by_key(|&(_, score, _)| score);
-    eprintln!("evals[depth = {} ({})]:", 63 - bit_board::get_tempo(my, opp),
-              if pruning { "lock" } else { "full" });
-    for i in 0 .. ::std::cmp::min(4, moves_scores_lines.len()) {
-        let (mv, score, line) = moves_scores_lines[i].clone();
-        let line: SVec<_> = line.into_iter().map(|x| disk_to_coord(x)).collect();
-        eprintln!("{:?}: {}{}", -score, coord_to_string(mv),
-                  line_to_string(&line));
-    }
     *moves_and_scores = moves_scores_lines.into_iter()
         .map(|(mv, score, _)| (mv, Score::Ended(score))).collect();
+    true
+}
+
+/// A move searched by the parallel endgame solver, with the result of
+/// `ai_eval_till_end_internal` for the position after it.
+struct Searched {
+    disk: u64,
+    score: i16,
+    line: SVec<u64>,
+    defunct: bool,
+}
+
+/// A move of `ai_eval_till_end`, for observers.
+fn solved_move(child: &Searched) -> RootMove {
+    let line: SVec<Coord> = child.line.iter().rev().map(|&disk| disk_to_coord(disk)).collect();
+    root_move(disk_to_coord(child.disk), Score::Ended(-child.score), &line)
 }
 
+/// Like `ai_eval_till_end_internal`, but splits the search between threads
+/// (Young Brothers Wait): once the first move is searched, the others are searched
+/// in parallel with the best score so far as alpha. With less than
+/// `PARALLEL_MIN_EMPTIES` empty cells, or a single search thread, the serial solver takes over.
+fn ai_eval_till_end_parallel(my: u64, opp: u64, alpha: i16, beta: i16,
+                             pruning: bool,
+                             ctx: &SearchContext,
+                             nnodes: &AtomicU64)
+                             -> (i16, SVec<u64>, bool) {
+    if 64 - bit_board::get_tempo(my, opp) < PARALLEL_MIN_EMPTIES || ctx.config.threads() == 1 {
+        let mut nodes = 0;
+        let result = ai_eval_till_end_internal(my, opp, alpha, beta, pruning, ctx, &mut nodes);
+        nnodes.fetch_add(nodes, Ordering::Relaxed);
+        return result;
+    }
+    nnodes.fetch_add(1, Ordering::Relaxed);
+    if ctx.deadline.check() {
+        return (alpha, SVec::new(), true);
+    }
+    let moves = bit_board::valid_moves_set(my, opp);
+    if moves == 0 && bit_board::valid_moves_set(opp, my) == 0 {
+        let score = get_score_diff(my, opp);
+        return (score, SVec::new(), false);
+    }
+
+    let mut tt_move = 0;
+    if let Some(entry) = ctx.tt.probe(my, opp) {
+        tt_move = entry.best_move;
+        if let Some(result) = tt_cutoff(&entry, alpha, beta, pruning) {
+            return result;
+        }
+    }
+
+    if moves == 0 {
+        let (score, mut line, defunct) = ai_eval_till_end_parallel(opp, my,
+        -beta, -alpha, pruning, ctx, nnodes);
+        if defunct {
+            return (-score, SVec::new(), true);
+        }
+        line.push(0); // Pass
+        return (-score, line, false);
+    }
+
+    let disks = ordered_moves(my, opp, moves, tt_move, ctx.config.endgame.parity);
+    let (&(_, disk, nopp, nmy), young) = disks.split_first().expect("There is a legal move");
+    let (score, line, defunct) = ai_eval_till_end_parallel(nopp, nmy, -beta, -alpha, pruning, ctx, nnodes);
+    let mut searched = vec![Searched { disk, score, line, defunct }];
+    if !ctx.deadline.has_expired() && -score < beta && !(pruning && score < 0) {
+        searched.extend(young_brothers(young, max(alpha, -score), beta, pruning, ctx, nnodes));
+    }
+    if ctx.deadline.has_expired() {
+        return (alpha, SVec::new(), true);
+    }
+
+    // The best moves first, as they would have been found by a serial search,
+    // and exact scores before bounds
+    searched.sort_by_key(|child| (child.score, child.defunct));
+    let mut ma = alpha;
+    let mut line = SVec::new();
+    let mut found = false;
+    let mut best_move = tt_move;
+    for child in searched {
+        if ma < -child.score {
+            ma = -child.score;
+            best_move = child.disk;
+            if !child.defunct {
+                let mut newline = child.line;
+                newline.push(child.disk);
+                line = newline;
+                found = true;
+            }
+        }
+        if ma >= beta {
+            ctx.tt.store(my, opp, SOLVED, Bound::Lower, Score::Ended(ma), child.disk);
+            return (ma, SVec::new(), true);
+        }
+        if pruning && child.score < 0 {
+            ctx.tt.store(my, opp, SOLVED, Bound::Lower, Score::Ended(1), child.disk);
+            return (ma, line, !found);
+        }
+    }
+    if pruning {
+        ctx.tt.store(my, opp, 0, Bound::Upper, Score::Ended(64), best_move);
+    } else if found {
+        ctx.tt.store(my, opp, SOLVED, Bound::Exact, Score::Ended(ma), best_move);
+    } else {
+        ctx.tt.store(my, opp, SOLVED, Bound::Upper, Score::Ended(ma), best_move);
+    }
+    (ma, line, !found)
+}
 
+/// Searches the moves of a position in parallel, with `alpha` raised as their scores
+/// come in. Stops once a move reaches `beta` or, in lock mode, wins.
+/// The moves whose search was stopped are left out, but their nodes are counted in `nnodes`.
+fn young_brothers<K: Sync>(disks: &[Child<K>], alpha: i16, beta: i16,
+                           pruning: bool,
+                           ctx: &SearchContext,
+                           nnodes: &AtomicU64)
+                           -> Vec<Searched> {
+    let stop = ctx.deadline.child();
+    let ctx = SearchContext { deadline: &stop, ..*ctx };
+    let best = AtomicIsize::new(alpha as isize);
+    let search = |&(_, disk, nopp, nmy): &Child<K>| {
+        let alpha = best.load(Ordering::Relaxed) as i16;
+        let (score, line, defunct) =
+            ai_eval_till_end_parallel(nopp, nmy, -beta, -alpha, pruning, &ctx, nnodes);
+        if stop.has_expired() {
+            return None;
+        }
+        best.fetch_max(-score as isize, Ordering::Relaxed);
+        if -score >= beta || (pruning && score < 0) {
+            stop.expire();
+        }
+        Some(Searched { disk, score, line, defunct })
+    };
+    if ctx.config.threads() > 1 {
+        disks.par_iter().filter_map(search).collect()
+    } else {
+        disks.iter().filter_map(search).collect()
+    }
+}
 
 // Check only if it's winning or not
 fn ai_eval_till_end_internal(my: u64, opp: u64, alpha: i16, beta: i16,
                              pruning: bool,
+                             ctx: &SearchContext,
                              nnodes: &mut u64)
                              -> (i16, SVec<u64>, bool) {
+    let options = ctx.config.endgame;
+    let empties = 64 - bit_board::get_tempo(my, opp);
+    if options.last_moves && empties <= LAST_MOVES {
+        let (score, disk) = solve_last(my, opp, alpha, beta, options.parity, nnodes);
+        let mut line = SVec::new();
+        if disk != 0 {
+            line.push(disk);
+        }
+        return if score >= beta {
+            (score, SVec::new(), true)
+        } else if score <= alpha {
+            (alpha, SVec::new(), true)
+        } else {
+            (score, line, false)
+        };
+    }
     *nnodes += 1;
-    let mut moves = bit_board::valid_moves_set(my, opp);
+    if ctx.deadline.check() {
+        return (alpha, SVec::new(), true);
+    }
+    let moves = bit_board::valid_moves_set(my, opp);
     if moves == 0 && bit_board::valid_moves_set(opp, my) == 0 {
         let score = get_score_diff(my, opp);
         return (score, SVec::new(), false);
     }
+    // The opponent keeps its stable disks, which bounds the score (tried on all its disks first)
+    if options.stability && alpha >= 64 - 2 * opp.count_ones() as i16 &&
+        alpha >= 64 - 2 * bit_board::stable_disks(opp, my).count_ones() as i16 {
+        return (alpha, SVec::new(), true);
+    }
+
+    // Near the leaves the table costs more than it saves
+    let use_tt = empties > TT_MIN_EMPTIES;
+    let mut tt_move = 0;
+    if let Some(entry) = if use_tt { ctx.tt.probe(my, opp) } else { None } {
+        tt_move = entry.best_move;
+        if let Some(result) = tt_cutoff(&entry, alpha, beta, pruning) {
+            return result;
+        }
+    }
 
     if moves == 0 {
         let (score, mut line, defunct) = ai_eval_till_end_internal(opp, my,
-        -beta, -alpha, pruning, nnodes);
+        -beta, -alpha, pruning, ctx, nnodes);
         if defunct {
             return (-score, SVec::new(), true);
         }
@@ -262,24 +1117,26 @@ fn ai_eval_till_end_internal(my: u64, opp: u64, alpha: i16, beta: i16,
         return (-score, line, false);
     }
 
-    let mut disks = SVec::new();
-    while moves != 0 {
-        let disk = 1u64 << moves.trailing_zeros();
-        moves ^= disk;
-        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
-        let opp_moves = bit_board::valid_moves_set(nopp, nmy);
-        disks.push((opp_moves.count_ones(), disk, nopp, nmy));
+    let disks = ordered_moves(my, opp, moves, tt_move, options.parity);
+    if use_tt && options.etc {
+        if let Some((score, disk)) = etc_cutoff(ctx.tt, &disks, beta) {
+            ctx.tt.store(my, opp, SOLVED, Bound::Lower, Score::Ended(score), disk);
+            return (score, SVec::new(), true);
+        }
     }
-    disks.sort_unstable_by_key(|&(sc, _, _, _)| sc);
     let mut ma = alpha;
     let mut line = SVec::new();
     let mut found = false;
+    let mut best_move = tt_move;
     for (_, disk, nopp, nmy) in disks {
         let (new_score, mut newline, defunct) =
-            ai_eval_till_end_internal(nopp, nmy, -beta, -ma, pruning,
-                                      nnodes);
+            ai_eval_till_end_internal(nopp, nmy, -beta, -ma, pruning, ctx, nnodes);
+        if ctx.deadline.has_expired() {
+            return (alpha, SVec::new(), true);
+        }
         if ma < -new_score {
             ma = -new_score;
+            best_move = disk;
             if !defunct {
                 newline.push(disk);
                 line = newline;
@@ -287,16 +1144,132 @@ fn ai_eval_till_end_internal(my: u64, opp: u64, alpha: i16, beta: i16,
             }
         }
         if ma >= beta {
+            if use_tt {
+                ctx.tt.store(my, opp, SOLVED, Bound::Lower, Score::Ended(ma), disk);
+            }
             return (ma, SVec::new(), true);
         }
         // Opponent always lose, no need to search more in lock mode
         if pruning && new_score < 0 {
-            break;
+            if use_tt {
+                // Lock mode only proves the sign of the score
+                ctx.tt.store(my, opp, SOLVED, Bound::Lower, Score::Ended(1), disk);
+            }
+            return (ma, line, !found);
+        }
+    }
+    if use_tt {
+        if pruning {
+            // Not a proven bound, but the move is still a good ordering hint
+            ctx.tt.store(my, opp, 0, Bound::Upper, Score::Ended(64), best_move);
+        } else if found {
+            ctx.tt.store(my, opp, SOLVED, Bound::Exact, Score::Ended(ma), best_move);
+        } else {
+            ctx.tt.store(my, opp, SOLVED, Bound::Upper, Score::Ended(ma), best_move);
         }
     }
     (ma, line, !found)
 }
 
+/// The moves of a position with the positions after them:
+/// the move suggested by the transposition table first, then by the number of replies
+/// (fastest first) and, with `parity`, the moves in odd quadrants first.
+fn ordered_moves(my: u64, opp: u64, mut moves: u64, tt_move: u64, parity: bool)
+                 -> SVec<Child<(bool, u32, bool)>> {
+    let odd = if parity { odd_quadrants(!(my | opp)) } else { 0 };
+    let mut disks = SVec::new();
+    while moves != 0 {
+        let disk = 1u64 << moves.trailing_zeros();
+        moves ^= disk;
+        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+        let opp_moves = bit_board::valid_moves_set(nopp, nmy);
+        disks.push(((disk != tt_move, opp_moves.count_ones(), disk & odd == 0), disk, nopp, nmy));
+    }
+    disks.sort_unstable_by_key(|&(sc, _, _, _)| sc);
+    disks
+}
+
+/// The quadrants with an odd number of `empty` cells.
+fn odd_quadrants(empty: u64) -> u64 {
+    QUADRANTS.iter()
+        .filter(|&&quadrant| (empty & quadrant).count_ones() % 2 == 1)
+        .fold(0, |odd, &quadrant| odd | quadrant)
+}
+
+/// The fail-soft score of a position with at most `LAST_MOVES` empty cells, with
+/// the best move (0 for a pass or the end of the game).
+/// There are too few moves to be worth generating and ordering, so every empty cell
+/// is tried in turn (in odd quadrants first, with `parity`), without the table.
+fn solve_last(my: u64, opp: u64, mut alpha: i16, beta: i16, parity: bool, nnodes: &mut u64) -> (i16, u64) {
+    *nnodes += 1;
+    let empty = !(my | opp);
+    let odd = if parity { odd_quadrants(empty) } else { 0 };
+    let mut best: Option<(i16, u64)> = None;
+    for &cells in [empty & odd, empty & !odd].iter() {
+        let mut cells = cells;
+        while cells != 0 {
+            let disk = 1u64 << cells.trailing_zeros();
+            cells ^= disk;
+            let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+            if nopp == opp {
+                // Nothing flipped, not a legal move
+                continue;
+            }
+            let score = if empty == disk {
+                get_score_diff(nmy, nopp)
+            } else {
+                -solve_last(nopp, nmy, -beta, -alpha, parity, nnodes).0
+            };
+            if best.is_none_or(|(best, _)| score > best) {
+                best = Some((score, disk));
+                alpha = max(alpha, score);
+                if alpha >= beta {
+                    return (score, disk);
+                }
+            }
+        }
+    }
+    match best {
+        Some(best) => best,
+        None if bit_board::valid_moves_set(opp, my) == 0 => (get_score_diff(my, opp), 0),
+        None => (-solve_last(opp, my, -beta, -alpha, parity, nnodes).0, 0),
+    }
+}
+
+/// Enhanced transposition cutoff: a move after which the table proves a score of
+/// at least `beta`, with that score.
+fn etc_cutoff<K>(tt: &TranspositionTable, disks: &[Child<K>], beta: i16) -> Option<(i16, u64)> {
+    disks.iter()
+        .filter_map(|&(_, disk, nopp, nmy)| match tt.probe(nopp, nmy) {
+            Some(Entry { depth: SOLVED, bound: Bound::Exact, score: Score::Ended(score), .. }) |
+            Some(Entry { depth: SOLVED, bound: Bound::Upper, score: Score::Ended(score), .. })
+                if -score >= beta => Some((-score, disk)),
+            _ => None,
+        })
+        .next()
+}
+
+/// The result of the endgame solver for a position, if its entry in the transposition
+/// table is enough to tell it.
+fn tt_cutoff(entry: &Entry, alpha: i16, beta: i16, pruning: bool) -> Option<(i16, SVec<u64>, bool)> {
+    let score = match (entry.depth, entry.score) {
+        (SOLVED, Score::Ended(score)) => score,
+        _ => return None,
+    };
+    let mut line = SVec::new();
+    line.push(entry.best_move);
+    match entry.bound {
+        Bound::Exact if score >= beta => Some((score, SVec::new(), true)),
+        Bound::Exact if score <= alpha => Some((alpha, SVec::new(), true)),
+        Bound::Exact => Some((score, line, false)),
+        Bound::Lower if score >= beta => Some((score, SVec::new(), true)),
+        // A proven win is all lock mode needs to know
+        Bound::Lower if pruning && score > 0 => Some((score, line, false)),
+        Bound::Upper if score <= alpha => Some((alpha, SVec::new(), true)),
+        _ => None,
+    }
+}
+
 fn my_board_eval(my: u64, opp: u64) -> f64 {
     let mut val = 0.0;
     let mylegit = bit_board::valid_moves_set(my, opp).count_ones();
@@ -334,6 +1307,17 @@ fn get_score_diff(my: u64, opp: u64) -> i16 {
     my.count_ones() as i16 - opp.count_ones() as i16
 }
 
+/// The smallest score above `s` that the search tells apart from it.
+fn next_score(s: Score) -> Score {
+    match s {
+        Score::Running(f) => Score::Running(f + NULL_WINDOW),
+        // Running scores lie between the lost and the won games, around the drawn ones
+        Score::Ended(-1) => Score::Running(f64::NEG_INFINITY),
+        Score::Ended(0) => Score::Running(NULL_WINDOW),
+        Score::Ended(d) => Score::Ended(d + 1),
+    }
+}
+
 fn negate_score(s: Score) -> Score {
     match s {
         Score::Running(f) => Score::Running(-f),
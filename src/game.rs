@@ -0,0 +1,213 @@
+//! The game model, built on `BitBoard`s: positions with the side to move (passing when
+//! it has no legal move), the end of the game, and games between two players,
+//! with the history to undo moves.
+
+use {Result};
+use bit_board;
+use bit_board::BitBoard;
+
+pub const BOARD_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Dark,
+    Light,
+}
+
+impl Side {
+    pub fn opposite(&self) -> Side {
+        match *self {
+            Side::Dark => Side::Light,
+            Side::Light => Side::Dark,
+        }
+    }
+}
+
+/// A cell of the board, by row and column (from 0 to `BOARD_SIZE - 1`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Coord(usize, usize);
+
+impl Coord {
+    pub fn new(row: usize, col: usize) -> Coord {
+        Coord(row, col)
+    }
+
+    pub fn get_row(&self) -> usize {
+        self.0
+    }
+
+    pub fn get_col(&self) -> usize {
+        self.1
+    }
+
+    pub fn get_row_col(&self) -> (usize, usize) {
+        (self.0, self.1)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReversiError {
+    IllegalMove(Coord),
+    OutOfBoundCoord(Coord),
+    EndedGame(Turn),
+    NoUndo,
+}
+
+/// The side to move, or `None` once the game has ended.
+pub type State = Option<Side>;
+
+/// A position of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Turn {
+    board: BitBoard,
+    state: State,
+}
+
+impl Turn {
+    /// The usual starting position.
+    pub fn first_turn() -> Turn {
+        Turn::from_bit_board(bit_board::START)
+    }
+
+    /// The position of a bitboard, where the other side moves if the side to move cannot.
+    pub fn from_bit_board(board: BitBoard) -> Turn {
+        if board.legal_moves() != 0 {
+            Turn { board, state: Some(board.side_to_move()) }
+        } else if !board.is_game_over() {
+            Turn { board: board.pass(), state: Some(board.side_to_move().opposite()) }
+        } else {
+            Turn { board, state: None }
+        }
+    }
+
+    pub fn get_bit_board(&self) -> BitBoard {
+        self.board
+    }
+
+    /// The side of the disk on a cell, if any.
+    pub fn get_cell(&self, coord: Coord) -> Result<Option<Side>> {
+        self.board.cell(coord)
+    }
+
+    pub fn get_state(&self) -> State {
+        self.state
+    }
+
+    pub fn is_end_state(&self) -> bool {
+        self.state.is_none()
+    }
+
+    /// The disks of each side, as `(dark, light)`.
+    pub fn get_score(&self) -> (u16, u16) {
+        (self.board.disc_count(Side::Dark) as u16, self.board.disc_count(Side::Light) as u16)
+    }
+
+    /// Light's disks minus Dark's.
+    pub fn get_score_diff(&self) -> i16 {
+        let (dark, light) = self.get_score();
+        light as i16 - dark as i16
+    }
+
+    /// The legal moves of the side to move, none if the game has ended.
+    pub fn get_moves(&self) -> Vec<Coord> {
+        self.board.moves().collect()
+    }
+
+    pub fn check_move(&self, coord: Coord) -> Result<()> {
+        if self.state.is_none() {
+            return Err(ReversiError::EndedGame(*self));
+        }
+        self.board.play(coord).map(|_| ())
+    }
+
+    /// Plays a move of the side to move, passing the next turn if the opponent cannot move.
+    pub fn make_move(&mut self, coord: Coord) -> Result<()> {
+        if self.state.is_none() {
+            return Err(ReversiError::EndedGame(*self));
+        }
+        *self = Turn::from_bit_board(self.board.play(coord)?);
+        Ok(())
+    }
+}
+
+impl From<BitBoard> for Turn {
+    fn from(board: BitBoard) -> Turn {
+        Turn::from_bit_board(board)
+    }
+}
+
+/// What a player does in its turn: a move, undoing its last move, or something else.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerAction<A> {
+    Move(Coord),
+    Undo,
+    Other(A),
+}
+
+pub trait IsPlayer<A> {
+    fn make_move(&self, turn: &Turn) -> Result<PlayerAction<A>>;
+}
+
+/// A game between two players, from the starting position.
+pub struct Game<'a, A: 'a> {
+    turn: Turn,
+    /// The positions before the current one.
+    history: Vec<Turn>,
+    dark: &'a dyn IsPlayer<A>,
+    light: &'a dyn IsPlayer<A>,
+}
+
+impl<'a, A> Game<'a, A> {
+    pub fn new(dark: &'a dyn IsPlayer<A>, light: &'a dyn IsPlayer<A>) -> Game<'a, A> {
+        Game {
+            turn: Turn::first_turn(),
+            history: Vec::new(),
+            dark,
+            light,
+        }
+    }
+
+    pub fn get_current_turn(&self) -> &Turn {
+        &self.turn
+    }
+
+    pub fn get_current_state(&self) -> State {
+        self.turn.get_state()
+    }
+
+    pub fn get_current_score(&self) -> (u16, u16) {
+        self.turn.get_score()
+    }
+
+    pub fn is_endgame(&self) -> bool {
+        self.turn.is_end_state()
+    }
+
+    /// Asks the side to move for its action and carries it out.
+    /// Undoing goes back to the last position where the same side was to move.
+    pub fn play_turn(&mut self) -> Result<PlayerAction<A>> {
+        let side = self.turn.get_state().ok_or(ReversiError::EndedGame(self.turn))?;
+        let action = match side {
+            Side::Dark => self.dark.make_move(&self.turn)?,
+            Side::Light => self.light.make_move(&self.turn)?,
+        };
+        match action {
+            PlayerAction::Move(coord) => {
+                let mut next = self.turn;
+                next.make_move(coord)?;
+                self.history.push(self.turn);
+                self.turn = next;
+            }
+            PlayerAction::Undo => {
+                let back = self.history
+                    .iter()
+                    .rposition(|turn| turn.get_state() == Some(side))
+                    .ok_or(ReversiError::NoUndo)?;
+                self.turn = self.history[back];
+                self.history.truncate(back);
+            }
+            PlayerAction::Other(_) => (),
+        }
+        Ok(action)
+    }
+}
@@ -0,0 +1,326 @@
+//! Games in the Generic Game Format (GGF), as used by GGS and many other Othello tools.
+//!
+//! ```text
+//! (;GM[Othello]PC[RUSThello]DT[2017.05.21]PB[Human]PW[Custom AI]RE[+2.000]TI[5:00/0:03/0:00]
+//! TY[8]BO[8 ---------------------------O*------*O--------------------------- *]
+//! B[F5//4.21]W[D6/-1.50/0.53]...;)
+//! ```
+//!
+//! Every move carries its color, and optionally the mover's evaluation (in disks)
+//! and the seconds spent on it. Passes are explicit (`PA`).
+
+use game::{Coord, Side};
+use bit_board;
+use bit_board::BitBoard;
+use clock;
+use record;
+use record::GameRecord;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The token used for passes.
+const PASS: &str = "PA";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GgfMove {
+    /// The cell played, or `None` for a pass.
+    pub coord: Option<Coord>,
+    /// The mover's evaluation of the move, in disks.
+    pub eval: Option<f64>,
+    /// The seconds spent on the move.
+    pub time: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GgfGame {
+    pub place: String,
+    pub date: String,
+    pub black: String,
+    pub white: String,
+    /// The clock (`TI`), as `time/increment/extension`.
+    pub time_control: String,
+    /// The result (`RE`), as Black's disk difference (e.g. `+2.000`), or `?`.
+    pub result: String,
+    /// The starting position (`BO`).
+    pub start: BitBoard,
+    /// The moves played, passes included.
+    pub moves: Vec<GgfMove>,
+}
+
+impl GgfGame {
+    /// Converts a game record, given the seconds spent on each of its moves (if known).
+    /// Passes are inserted where needed.
+    pub fn from_record(record: &GameRecord, times: &[Option<f64>], time_control: &str)
+                       -> ::std::result::Result<GgfGame, String> {
+        let mut moves = Vec::new();
+        let mut board = bit_board::START;
+        for (n, &coord) in record.moves.iter().enumerate() {
+            if board.legal_moves() == 0 {
+                moves.push(GgfMove { coord: None, eval: None, time: None });
+                board = board.pass();
+            }
+            board = record::play(board, coord)
+                .ok_or_else(|| format!("move {} ({}) is illegal", n + 1, record::format_coord(coord)))?;
+            moves.push(GgfMove {
+                coord: Some(coord),
+                eval: record.evals.get(n).cloned().unwrap_or(None),
+                time: times.get(n).cloned().unwrap_or(None),
+            });
+        }
+        Ok(GgfGame {
+            place: "RUSThello".to_string(),
+            date: record.date.clone(),
+            black: record.dark.clone(),
+            white: record.light.clone(),
+            time_control: time_control.to_string(),
+            result: match record.time_loser() {
+                Some(Side::Dark) => format!("-64.000{}", record::TIME_LOSS),
+                Some(Side::Light) => format!("+64.000{}", record::TIME_LOSS),
+                None => final_result(board),
+            },
+            start: bit_board::START,
+            moves,
+        })
+    }
+
+    /// Converts to a game record, provided the game starts from the usual position.
+    pub fn to_record(&self) -> ::std::result::Result<GameRecord, String> {
+        if self.start != bit_board::START {
+            return Err("the game does not start from the usual position".to_string());
+        }
+        let final_board = *self.positions()?.last().expect("There is at least the starting position");
+        let mut record = GameRecord::new(&self.black, &self.white);
+        record.date = self.date.clone();
+        record.moves = self.moves.iter().filter_map(|mv| mv.coord).collect();
+        record.evals = self.moves.iter().filter(|mv| mv.coord.is_some()).map(|mv| mv.eval).collect();
+        if self.result.ends_with(record::TIME_LOSS) {
+            record.set_time_loss(if self.result.starts_with('-') { Side::Dark } else { Side::Light });
+        } else if final_board.is_game_over() {
+            record.set_result(final_board.disc_count(Side::Dark) as u16,
+                              final_board.disc_count(Side::Light) as u16);
+        }
+        Ok(record)
+    }
+
+    /// The first game in the given file.
+    pub fn load(path: &str) -> ::std::result::Result<GgfGame, String> {
+        read_file(path)?.parse()
+    }
+
+    /// All the games in the given file.
+    pub fn load_all(path: &str) -> ::std::result::Result<Vec<GgfGame>, String> {
+        parse_all(&read_file(path)?)
+    }
+
+    pub fn save(&self, path: &str) -> ::std::result::Result<(), String> {
+        File::create(path)
+            .and_then(|mut file| file.write_all(self.to_string().as_bytes()))
+            .map_err(|err| format!("cannot write {}: {}", path, err))
+    }
+
+    /// The positions before each move, followed by the final one.
+    /// Fails if a move is illegal or played by the wrong side.
+    pub fn positions(&self) -> ::std::result::Result<Vec<BitBoard>, String> {
+        let mut positions = vec![self.start];
+        let mut board = self.start;
+        for (n, mv) in self.moves.iter().enumerate() {
+            board = match mv.coord {
+                None if board.legal_moves() == 0 && !board.is_game_over() => board.pass(),
+                None => return Err(format!("move {} (pass) is illegal", n + 1)),
+                Some(coord) => {
+                    board.play(coord)
+                        .map_err(|_| format!("move {} ({}) is illegal", n + 1, record::format_coord(coord)))?
+                }
+            };
+            positions.push(board);
+        }
+        Ok(positions)
+    }
+
+    /// The time left to the side to move and the increment of the clock, from the time
+    /// control and the seconds spent on the moves, if the game has a time control.
+    pub fn clock(&self) -> Option<(Duration, Duration)> {
+        let (initial, increment) = parse_time_control(&self.time_control)?;
+        let moves = self.moves.len();
+        // The moves of the side to move have the parity of the next one
+        let spent: f64 = self.moves.iter()
+            .enumerate()
+            .filter(|&(n, _)| n % 2 == moves % 2)
+            .filter_map(|(_, mv)| mv.time)
+            .sum();
+        let granted = initial + increment * (moves / 2) as u32;
+        Some((granted.checked_sub(duration(spent)).unwrap_or_default(), increment))
+    }
+}
+
+impl fmt::Display for GgfGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(;GM[Othello]PC[{}]DT[{}]PB[{}]PW[{}]RE[{}]TI[{}]TY[8]",
+               self.place, self.date, self.black, self.white, self.result, self.time_control)?;
+        // GGF writes black disks as `*`
+        write!(f, "BO[8 {}]", self.start.to_string().replace('X', "*"))?;
+        let mut black = self.start.black_to_move();
+        for mv in self.moves.iter() {
+            write!(f, "{}[{}]", if black { 'B' } else { 'W' }, mv)?;
+            black = !black;
+        }
+        writeln!(f, ";)")
+    }
+}
+
+impl fmt::Display for GgfMove {
+    /// Formats the move as `F5/eval/time`, leaving out what is unknown.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let coord = self.coord.map_or(PASS.to_string(), |coord| record::format_coord(coord).to_uppercase());
+        let eval = self.eval.map_or(String::new(), |eval| format!("{:.2}", eval));
+        let time = self.time.map_or(String::new(), |time| format!("{:.2}", time));
+        let text = format!("{}/{}/{}", coord, eval, time);
+        write!(f, "{}", text.trim_end_matches('/'))
+    }
+}
+
+impl FromStr for GgfGame {
+    type Err = String;
+
+    /// Parses the first game in `text`.
+    fn from_str(text: &str) -> ::std::result::Result<GgfGame, String> {
+        parse_all(text)?.into_iter().next().ok_or_else(|| "no game found".to_string())
+    }
+}
+
+/// Parses all the games in `text`.
+pub fn parse_all(text: &str) -> ::std::result::Result<Vec<GgfGame>, String> {
+    let mut games = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("(;") {
+        let end = rest[start ..].find(";)").ok_or_else(|| "unterminated game".to_string())? + start;
+        games.push(parse_game(&rest[start + 2 .. end])?);
+        rest = &rest[end + 2 ..];
+    }
+    Ok(games)
+}
+
+fn parse_game(text: &str) -> ::std::result::Result<GgfGame, String> {
+    let mut game = GgfGame {
+        place: String::new(),
+        date: String::new(),
+        black: String::new(),
+        white: String::new(),
+        time_control: String::new(),
+        result: "?".to_string(),
+        start: bit_board::START,
+        moves: Vec::new(),
+    };
+    let mut colors = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let open = rest.find('[').ok_or_else(|| format!("invalid property \"{}\"", rest.trim()))?;
+        let close = rest.find(']').ok_or_else(|| "unterminated property".to_string())?;
+        if close < open {
+            return Err(format!("invalid property \"{}\"", &rest[.. close + 1]));
+        }
+        let value = rest[open + 1 .. close].trim().to_string();
+        match rest[.. open].trim() {
+            "GM" if value.to_lowercase() != "othello" => return Err(format!("unsupported game \"{}\"", value)),
+            "TY" if !value.starts_with('8') => return Err(format!("unsupported board type \"{}\"", value)),
+            "PC" => game.place = value,
+            "DT" => game.date = value,
+            "PB" => game.black = value,
+            "PW" => game.white = value,
+            "TI" => game.time_control = value,
+            "RE" => game.result = value,
+            "BO" => game.start = parse_board(&value)?,
+            "B" | "W" => {
+                colors.push(rest[.. open].trim() == "B");
+                game.moves.push(parse_move(&value)?);
+            }
+            _ => (),
+        }
+        rest = rest[close + 1 ..].trim_start();
+    }
+    // Every move has to be played by the side to move
+    for (n, (board, &black)) in game.positions()?.iter().zip(colors.iter()).enumerate() {
+        if board.black_to_move() != black {
+            return Err(format!("move {} is played by the wrong side", n + 1));
+        }
+    }
+    Ok(game)
+}
+
+fn parse_board(text: &str) -> ::std::result::Result<BitBoard, String> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    if fields.len() < 3 || fields[0] != "8" {
+        return Err(format!("unsupported board \"{}\"", text));
+    }
+    fields[1 ..].concat().parse().map_err(|err| format!("invalid board \"{}\": {}", text, err))
+}
+
+/// Parses a move given as `F5/eval/time`, where only the cell (or `PA`) is required.
+pub fn parse_move(text: &str) -> ::std::result::Result<GgfMove, String> {
+    let mut fields = text.split('/');
+    let coord = match fields.next().unwrap_or("").trim() {
+        pass if pass.eq_ignore_ascii_case(PASS) => None,
+        coord => Some(record::parse_coord(coord).ok_or_else(|| format!("invalid move \"{}\"", text))?),
+    };
+    let eval = match fields.next().map(str::trim) {
+        Some(eval) if !eval.is_empty() => Some(eval.parse().map_err(|_| format!("invalid evaluation \"{}\"", eval))?),
+        _ => None,
+    };
+    let time = match fields.next().map(str::trim) {
+        Some(time) if !time.is_empty() => Some(parse_time(time).ok_or_else(|| format!("invalid time \"{}\"", time))?),
+        _ => None,
+    };
+    Ok(GgfMove {
+        coord,
+        eval,
+        time,
+    })
+}
+
+/// Parses a time given as `[[hh:]mm:]ss`, in seconds.
+fn parse_time(text: &str) -> Option<f64> {
+    // Clock options (e.g. `,L`) are not supported and ignored
+    let text = text.split(',').next().unwrap_or("");
+    text.split(':').try_fold(0.0, |secs, field| field.trim().parse::<f64>().ok().map(|field| secs * 60.0 + field))
+}
+
+/// Parses a GGF clock, `time/increment/extension`, as the initial time and the increment.
+/// The extension is ignored.
+pub fn parse_time_control(text: &str) -> Option<(Duration, Duration)> {
+    let mut fields = text.split('/');
+    let initial = parse_time(fields.next()?)?;
+    let increment = match fields.next().map(str::trim) {
+        Some(increment) if !increment.is_empty() => parse_time(increment)?,
+        _ => 0.0,
+    };
+    Some((duration(initial), duration(increment)))
+}
+
+fn duration(secs: f64) -> Duration {
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+/// Formats a time control as a GGF clock, without extension.
+pub fn format_time_control(initial: Duration, increment: Duration) -> String {
+    format!("{}/{}/0:00", clock::format_time(initial), clock::format_time(increment))
+}
+
+fn read_file(path: &str) -> ::std::result::Result<String, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|err| format!("cannot read {}: {}", path, err))?;
+    Ok(text)
+}
+
+/// The result of a game ending in `board`, or `?` if it is not over.
+fn final_result(board: BitBoard) -> String {
+    if board.is_game_over() {
+        format!("{:+.3}", board.disc_count(Side::Dark) as f64 - board.disc_count(Side::Light) as f64)
+    } else {
+        "?".to_string()
+    }
+}
@@ -0,0 +1,258 @@
+//! An engine mode speaking the Go Text Protocol (GTP), so that GUIs and tournament
+//! managers can drive RUSThello over stdin/stdout.
+//!
+//! Vertices use the usual Othello notation (`A1` is the top left corner),
+//! and colors are `black` (Dark) and `white` (Light).
+
+use game::{IsPlayer, PlayerAction, Side, Turn};
+use ai_player::{AiKind, AiPlayer, TimeControl};
+use bit_board;
+use custom_ai::SearchConfig;
+use record;
+use std::io;
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+const COMMANDS: [&str; 17] = ["protocol_version",
+                              "name",
+                              "version",
+                              "known_command",
+                              "list_commands",
+                              "quit",
+                              "boardsize",
+                              "clear_board",
+                              "komi",
+                              "play",
+                              "genmove",
+                              "undo",
+                              "showboard",
+                              "final_score",
+                              "time_settings",
+                              "time_left",
+                              "rusthello-ai"];
+
+pub struct Engine {
+    /// The positions of the game so far, the current one last.
+    history: Vec<Turn>,
+    /// The AI answering `genmove`.
+    ai: AiPlayer,
+}
+
+impl Engine {
+    /// An engine whose AI searches with `config`.
+    pub fn new(config: SearchConfig) -> Engine {
+        Engine {
+            history: vec![Turn::first_turn()],
+            ai: AiPlayer::new(AiKind::Custom, config),
+        }
+    }
+
+    /// The time control of the AI, if it is timed.
+    pub fn time_control(&self) -> Option<&TimeControl> {
+        match self.ai.kind {
+            AiKind::Timed(ref time_control) => Some(time_control),
+            _ => None,
+        }
+    }
+
+    fn turn(&self) -> &Turn {
+        self.history.last().expect("There is at least the first turn")
+    }
+
+    /// Executes a command, returning its response or error message,
+    /// and whether the engine has to quit.
+    pub fn execute(&mut self, command: &str, args: &[&str]) -> (Result<String, String>, bool) {
+        let response = match command {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok("RUSThello".to_string()),
+            "version" => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            "known_command" => Ok(args.first().is_some_and(|arg| COMMANDS.contains(arg)).to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => return (Ok(String::new()), true),
+            "boardsize" => {
+                match args.first().map(|size| size.parse::<usize>()) {
+                    Some(Ok(8)) => Ok(String::new()),
+                    Some(Ok(_)) => Err("unacceptable size".to_string()),
+                    _ => Err("syntax error".to_string()),
+                }
+            }
+            "clear_board" => {
+                self.history = vec![Turn::first_turn()];
+                Ok(String::new())
+            }
+            "komi" => Ok(String::new()),
+            "play" => self.play(args),
+            "genmove" => self.genmove(args),
+            "undo" => {
+                if self.history.len() > 1 {
+                    self.history.pop();
+                    Ok(String::new())
+                } else {
+                    Err("cannot undo".to_string())
+                }
+            }
+            "showboard" => {
+                Ok(format!("\n{}", bit_board::show_bit_board(self.turn().get_bit_board())))
+            }
+            "final_score" => {
+                let (dark, light) = self.turn().get_score();
+                Ok(if dark > light {
+                    format!("B+{}", dark - light)
+                } else if light > dark {
+                    format!("W+{}", light - dark)
+                } else {
+                    "0".to_string()
+                })
+            }
+            "time_settings" => self.time_settings(args),
+            "time_left" => self.time_left(args),
+            "rusthello-ai" => {
+                match args.first().map(|arg| arg.to_lowercase()) {
+                    Some(ref ai) if ai == "weak" => self.ai.kind = AiKind::Weak,
+                    Some(ref ai) if ai == "medium" => self.ai.kind = AiKind::Medium,
+                    Some(ref ai) if ai == "strong" => self.ai.kind = AiKind::Strong,
+                    Some(ref ai) if ai == "custom" => self.ai.kind = AiKind::Custom,
+                    _ => return (Err("unknown ai".to_string()), false),
+                }
+                Ok(String::new())
+            }
+            _ => Err("unknown command".to_string()),
+        };
+        (response, false)
+    }
+
+    fn play(&mut self, args: &[&str]) -> Result<String, String> {
+        if args.len() < 2 {
+            return Err("syntax error".to_string());
+        }
+        let side = parse_color(args[0])?;
+        let mut turn = *self.turn();
+        if args[1].to_lowercase() == "pass" {
+            // Passes are made by `Turn` on its own, so they only have to be legal
+            if legal_moves(&turn, side) != 0 {
+                return Err("illegal move".to_string());
+            }
+        } else {
+            let coord = record::parse_coord(args[1]).ok_or_else(|| "syntax error".to_string())?;
+            if turn.get_state() != Some(side) || turn.make_move(coord).is_err() {
+                return Err("illegal move".to_string());
+            }
+        }
+        self.history.push(turn);
+        Ok(String::new())
+    }
+
+    fn genmove(&mut self, args: &[&str]) -> Result<String, String> {
+        let side = parse_color(args.first().cloned().unwrap_or(""))?;
+        let mut turn = *self.turn();
+        if turn.get_state() != Some(side) {
+            if legal_moves(&turn, side) != 0 {
+                return Err(format!("it is not {}'s turn", args[0]));
+            }
+            self.history.push(turn);
+            return Ok("pass".to_string());
+        }
+        let coord = match self.ai.make_move(&turn) {
+            Ok(PlayerAction::Move(coord)) => coord,
+            _ => return Err("no move found".to_string()),
+        };
+        turn.make_move(coord).map_err(|_| "illegal move".to_string())?;
+        self.history.push(turn);
+        Ok(record::format_coord(coord).to_uppercase())
+    }
+
+    /// Sets a main time and a Canadian byo-yomi of `stones` moves.
+    /// The byo-yomi is played as an increment of the main time.
+    fn time_settings(&mut self, args: &[&str]) -> Result<String, String> {
+        let values: Vec<f64> = args.iter().filter_map(|arg| arg.parse().ok()).collect();
+        if values.len() != 3 || args.len() != 3 {
+            return Err("syntax error".to_string());
+        }
+        let (main_time, byo_yomi, stones) = (values[0], values[1], values[2].max(1.0));
+        self.ai.kind = if main_time <= 0.0 && byo_yomi <= 0.0 {
+            AiKind::Custom
+        } else if main_time <= 0.0 {
+            AiKind::Timed(TimeControl::PerMove(secs_to_duration(byo_yomi / stones)))
+        } else {
+            AiKind::Timed(TimeControl::game(secs_to_duration(main_time), secs_to_duration(byo_yomi / stones)))
+        };
+        Ok(String::new())
+    }
+
+    fn time_left(&mut self, args: &[&str]) -> Result<String, String> {
+        if args.len() != 3 {
+            return Err("syntax error".to_string());
+        }
+        let side = parse_color(args[0])?;
+        let (time, stones) = match (args[1].parse::<f64>(), args[2].parse::<u32>()) {
+            (Ok(time), Ok(stones)) => (time, stones),
+            _ => return Err("syntax error".to_string()),
+        };
+        // Only the engine's own time matters
+        if self.turn().get_state() == Some(side) {
+            if let AiKind::Timed(ref mut time_control) = self.ai.kind {
+                *time_control = match *time_control {
+                    _ if stones > 0 => TimeControl::PerMove(secs_to_duration(time / stones as f64)),
+                    TimeControl::Game { increment, .. } => TimeControl::game(secs_to_duration(time), increment),
+                    _ => TimeControl::game(secs_to_duration(time), Duration::from_secs(0)),
+                };
+            }
+        }
+        Ok(String::new())
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Engine {
+        Engine::new(SearchConfig::default())
+    }
+}
+
+/// Runs the engine, reading commands from `input` until `quit` or the end of the input.
+/// The AI searches with `config`.
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W, config: SearchConfig) -> io::Result<()> {
+    let mut engine = Engine::new(config);
+    for line in input.lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let id = if words[0].parse::<u32>().is_ok() { words.remove(0) } else { "" };
+        if words.is_empty() {
+            continue;
+        }
+        let (response, quit) = engine.execute(words[0], &words[1 ..]);
+        match response {
+            Ok(response) => write!(output, "={} {}\n\n", id, response)?,
+            Err(error) => write!(output, "?{} {}\n\n", id, error)?,
+        }
+        output.flush()?;
+        if quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn parse_color(color: &str) -> Result<Side, String> {
+    match &*color.to_lowercase() {
+        "b" | "black" | "dark" => Ok(Side::Dark),
+        "w" | "white" | "light" => Ok(Side::Light),
+        _ => Err("syntax error".to_string()),
+    }
+}
+
+/// The legal moves of `side` in `turn`, even if it is not its turn.
+fn legal_moves(turn: &Turn, side: Side) -> u64 {
+    let board = turn.get_bit_board();
+    match side {
+        Side::Dark => bit_board::valid_moves_set(board.black(), board.white()),
+        Side::Light => bit_board::valid_moves_set(board.white(), board.black()),
+    }
+}
+
+fn secs_to_duration(secs: f64) -> Duration {
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
@@ -2,11 +2,14 @@
 
 use std::string::String;
 use std::io::{self, Write};
-use reversi::Side;
-use reversi::board::{BOARD_SIZE, Coord};
-use reversi::game::PlayerAction;
-use reversi::turn::{State, Turn};
+use std::time::Duration;
+use game::{Coord, PlayerAction, Side, State, Turn, BOARD_SIZE};
 use {Result, Action, OtherAction};
+use ai_player::Score;
+use clock::{self, Clock};
+use custom_ai::{SearchInfo, SearchObserver};
+use openings;
+use record::{self, GameRecord};
 use termion::{color, style};
 
 // ANSI version
@@ -17,11 +20,13 @@ const LEGAL_MOVE: char = '○';
 
 pub enum UserCommand {
     NewGame,
+    LoadGame,
     HumanPlayer,
     AiWeak,
     AiMedium,
     AiStrong,
     AiCustom,
+    AiTimed,
     Help,
     Credits,
     Quit,
@@ -42,14 +47,15 @@ fn header(title: &str) -> String {
 								 	style::Reset)
 }
 
-const INTRO: &'static str = "\t  a simple Reversi game
+const INTRO: &str = "\t  a simple Reversi game
 \twritten in Rust with love";
 
 pub fn intro() {
     println!("{}\n{}\n\t        v. {}", header("RUSThello"), INTRO, env!("CARGO_PKG_VERSION"));
 }
 
-const MAIN_MENU: &'static str = "\tn - New match
+const MAIN_MENU: &str = "\tn - New match
+\tl - Load match
 \th - Help
 \tc - Credits
 \tq - Quit RUSThello";
@@ -58,30 +64,32 @@ pub fn main_menu() {
     println!("{}\n{}\n{}", header("MAIN MENU"), MAIN_MENU, ruler());
 }
 
-const NEW_PLAYER_MENU: &'static str = "\th - Human Player
+const NEW_PLAYER_MENU: &str = "\th - Human Player
 \tw - Weak   AI
 \tm - Medium AI
 \ts - Strong AI
 \tc - Custom AI
+\tt - Timed  AI
 \tq - Quit match";
 
 pub fn new_player_menu() {
     println!("{}\n{}\n{}", header("CHOOSE PLAYER"), NEW_PLAYER_MENU, ruler());
 }
 
-const COMMANDS_INFO: &'static str = "\n\n
+const COMMANDS_INFO: &str = "\n\n
 \tStarting new game...
 \tType a cell's coordinates to place your disk there.
 \tExaple: \"c4\" (or \"C4\", \"4c\", \"4C\", etc...).
 \tType 'help' or 'h' to display a help message.
 \tType 'undo' or 'u' to undo the last move.
+\tType 'save <file>' to save the game.
 \tType 'quit' or 'q' to abandon the game.";
 
 pub fn commands_info() {
     println!("{}", COMMANDS_INFO);
 }
 
-const HELP: &'static str = "\tReversi is a board game where two players compete against each other. \
+const HELP: &str = "\tReversi is a board game where two players compete against each other. \
 The game is played on a 8x8 board with green cells. \
 There are 64 identical pieces called disks that are white on one side and black on the other. \
 A player is Dark, using disks’ black side, and the other one is Light, using disks' white side. \
@@ -102,7 +110,7 @@ Sometimes a game also happens to end before that, leaving empty cells on the boa
 \tWhen the game ends, the player with the most disks wins. \
 Ties are possible as well, if both players have the same number of disks.";
 
-const RUSTHELLO: &'static str = "\tTo play RUSThello, you first have to choose who is playing on each side, Dark and Light. \
+const RUSTHELLO: &str = "\tTo play RUSThello, you first have to choose who is playing on each side, Dark and Light. \
 You can choose a human players or an AI. \
 Choose human for both players and challenge a friend, or test your skills against an AI, \
 or even relax as you watch two AIs competing against each other; any combination is possible!\n
@@ -112,6 +120,8 @@ E.g. all of 'c4', 'C4', '4c' and '4C' are valid and equivalent coordinates. \
 For ease of use, all legal moves on the board are highlighted.\n
 \tFurthermore, you can also input special commands:
 \t* 'undo' (or 'u') to undo your last move (and yes, you can 'undo' as many times as you like),
+\t* 'save <file>' to save the game as a transcript, which you can load later from the main menu \
+(name the file '.ggf' to save it in the Generic Game Format, along with the time spent on each move),
 \t* 'help' (or 'h') to see this help message again, and 'quit' (or 'q') to quit the game.";
 
 pub fn help() {
@@ -130,13 +140,17 @@ pub fn credits() {
 
 /// Reads user's input
 fn get_user_input() -> String {
+    get_raw_user_input().to_lowercase()
+}
+
+/// Reads user's input, preserving its case (e.g. for file names)
+fn get_raw_user_input() -> String {
     let _ = io::stdout().flush();
     let mut input = String::new();
     if io::stdin().read_line(&mut input).is_err() {
         panic!("\tFailed to read input!");
     }
-    input = input.trim().to_lowercase();
-    input
+    input.trim().to_string()
 }
 
 /// It gets an input from the user and tries to parse it, then returns a `Option<UserCommand>`.
@@ -147,6 +161,7 @@ pub fn input_main_menu() -> UserCommand {
     loop {
         match &*get_user_input() {
             "n" | "new game" => return UserCommand::NewGame,
+            "l" | "load" | "load game" => return UserCommand::LoadGame,
             "h" | "help" => return UserCommand::Help,
             "c" | "credits" => return UserCommand::Credits,
             "q" | "quit" | "exit" => return UserCommand::Quit,
@@ -171,6 +186,7 @@ pub fn choose_new_player(side: Side) -> UserCommand {
             "s" | "strong" | "strong ai" => return UserCommand::AiStrong,
             "q" | "quit" | "exit" => return UserCommand::Quit,
             "c" | "custom" => return UserCommand::AiCustom,
+            "t" | "timed" | "timed ai" => return UserCommand::AiTimed,
             _ => {
                 print!("\tInvalid command! Try again: ");
                 continue;
@@ -179,6 +195,105 @@ pub fn choose_new_player(side: Side) -> UserCommand {
     }
 }
 
+/// The name of a player, as recorded in saved games.
+pub fn player_name(command: &UserCommand) -> &'static str {
+    match *command {
+        UserCommand::HumanPlayer => "Human",
+        UserCommand::AiWeak => "Weak AI",
+        UserCommand::AiMedium => "Medium AI",
+        UserCommand::AiStrong => "Strong AI",
+        UserCommand::AiCustom => "Custom AI",
+        UserCommand::AiTimed => "Timed AI",
+        _ => "",
+    }
+}
+
+/// Asks for the name of a file to load a game from.
+pub fn choose_file() -> String {
+    print!("\tFile name: ");
+    get_raw_user_input()
+}
+
+/// Shows a loaded game and asks after how many of its moves to resume it.
+pub fn choose_resume_point(record: &GameRecord) -> usize {
+    println!("\n\t{}Dark{}: {}, {}Light{}: {}, {} ({})",
+             style::Bold,
+             style::Reset,
+             record.dark,
+             style::Bold,
+             style::Reset,
+             record.light,
+             record.date,
+             record.result);
+    let moves: Vec<String> = record.moves.iter().map(|&coord| record::format_coord(coord)).collect();
+    for (n, chunk) in moves.chunks(10).enumerate() {
+        println!("\t{:>2}. {}", n * 10 + 1, chunk.join(" "));
+    }
+    print!("\tResume after move (0-{}, empty for the last one): ", moves.len());
+    loop {
+        let input = get_user_input();
+        if input.is_empty() {
+            return moves.len();
+        }
+        match input.parse::<usize>() {
+            Ok(n) if n <= moves.len() => return n,
+            _ => {
+                print!("\tInvalid move number! Try again: ");
+                continue;
+            }
+        }
+    }
+}
+
+/// Asks for a file to save a finished game to.
+pub fn ask_save_file() -> Option<String> {
+    print!("\tSave the game as (empty to skip): ");
+    let input = get_raw_user_input();
+    if input.is_empty() { None } else { Some(input) }
+}
+
+/// Asks for the time control of the match, as minutes for each player plus
+/// seconds of increment per move (e.g. "5+3"). Returns `None` for an untimed match.
+pub fn choose_time_control() -> Option<(Duration, Duration)> {
+    print!("\tTime control (e.g. 5+3, empty for none): ");
+    loop {
+        let input = get_user_input();
+        if input.is_empty() {
+            return None;
+        }
+        let mut parts = input.splitn(2, '+');
+        let minutes = parts.next().unwrap_or("").trim().parse::<f64>();
+        let increment = parts.next().map_or(Ok(0.0), |secs| secs.trim().parse::<f64>());
+        match (minutes, increment) {
+            (Ok(minutes), Ok(increment)) if minutes > 0.0 && increment >= 0.0 => {
+                return Some((secs_to_duration(minutes * 60.0), secs_to_duration(increment)));
+            }
+            _ => {
+                print!("\tInvalid time control! Try again: ");
+                continue;
+            }
+        }
+    }
+}
+
+fn secs_to_duration(secs: f64) -> Duration {
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+/// Asks how many seconds per move a timed AI can think.
+pub fn choose_time_per_move() -> Duration {
+    print!("\tSeconds per move: ");
+    loop {
+        match get_user_input().parse::<f64>() {
+            Ok(secs) if secs > 0.0 => return secs_to_duration(secs),
+            _ => {
+                print!("\tInvalid time! Try again: ");
+                continue;
+            }
+        }
+    }
+}
+
 /// It `get_status` a human player's input and convert it into a move.
 /// If the move if illegal, it ask for another input until the given move is a legal one.
 pub fn human_make_move(turn: &Turn) -> Result<Action> {
@@ -193,34 +308,42 @@ pub fn human_make_move(turn: &Turn) -> Result<Action> {
     }
 
     loop {
-        let input = &*get_user_input();
+        let raw_input = get_raw_user_input();
+        let input = &*raw_input.to_lowercase();
         match input {
             "h" | "help" => return Ok(PlayerAction::Other(OtherAction::Help)),
             "u" | "undo" => return Ok(PlayerAction::Undo),
             "q" | "quit" => return Ok(PlayerAction::Other(OtherAction::Quit)),
+            "s" | "save" => {
+                print!("\tType 'save' followed by a file name: ");
+                continue;
+            }
+            _ if input.starts_with("save ") => {
+                return Ok(PlayerAction::Other(OtherAction::Save(raw_input[5..].trim().to_string())));
+            }
             _other_input => {
                 let mut row: Option<usize> = None;
                 let mut col: Option<usize> = None;
 
                 for curr_char in input.chars() {
                     match curr_char {
-                        '1'...'8' => row = Some(curr_char as usize - '1' as usize),
-                        'a'...'h' => col = Some(curr_char as usize - 'a' as usize),
+                        '1'..='8' => row = Some(curr_char as usize - '1' as usize),
+                        'a'..='h' => col = Some(curr_char as usize - 'a' as usize),
                         _ => {}
                     }
                 }
 
-                if row.is_none() || col.is_none() {
-                    print!("\tIllegal move, try again: ");
-                    continue;
-                } else {
-                    let coord = Coord::new(row.unwrap(), col.unwrap());
+                if let (Some(row), Some(col)) = (row, col) {
+                    let coord = Coord::new(row, col);
                     if turn.check_move(coord).is_ok() {
                         return Ok(PlayerAction::Move(coord));
                     } else {
                         print!("\tIllegal move, try again: ");
                         continue;
                     }
+                } else {
+                    print!("\tIllegal move, try again: ");
+                    continue;
                 }
             }
         }
@@ -228,18 +351,18 @@ pub fn human_make_move(turn: &Turn) -> Result<Action> {
 }
 
 /// `draw_board` draws the board (using text characters) in a pleasant-looking way.
-pub fn draw_board(turn: &Turn) {
-    let board = turn.get_board();
+/// If the match is timed, the time left to each player is shown below the score.
+pub fn draw_board(turn: &Turn, clock: Option<&Clock>) {
     let stdout = io::stdout();
     let mut board_to_string = stdout.lock();
     // Add column reference at the top
-    write!(board_to_string,
-           "\n\t{}                         {}\n",
+    writeln!(board_to_string,
+           "\n\t{}                         {}",
            color::Bg(color::LightGreen),
            color::Bg(color::Reset))
             .expect("Writing on buffer `board_to_string` failed!");
-    write!(board_to_string,
-           "\t{}{}     A B C D E F G H     {}{}\n",
+    writeln!(board_to_string,
+           "\t{}{}     A B C D E F G H     {}{}",
            color::Bg(color::LightGreen),
            color::Fg(color::Black),
            color::Fg(color::Reset),
@@ -262,10 +385,10 @@ pub fn draw_board(turn: &Turn) {
         // For every column, add the appropriate character depending on the content of the current cell
         for col in 0..BOARD_SIZE {
             let coord = Coord::new(row, col);
-            match *board.get_cell(coord).unwrap() {
+            match turn.get_cell(coord).unwrap() {
                 // Light and Dark cells are represented by white and black bullets
-                Some(disk) => {
-                    match disk.get_side() {
+                Some(side) => {
+                    match side {
                             Side::Dark => {
                                 write!(board_to_string,
                                        "{}{}{}",
@@ -321,8 +444,8 @@ pub fn draw_board(turn: &Turn) {
         write!(board_to_string, "{}", color::Bg(color::Reset)).expect("Writing on buffer `board_to_string` failed!");
 
         // Add a row reference to the right
-        write!(board_to_string,
-               "{} {}{}{}  {}\n",
+        writeln!(board_to_string,
+               "{} {}{}{}  {}",
                color::Bg(color::LightGreen),
                color::Fg(color::Black),
                row + 1,
@@ -355,7 +478,7 @@ pub fn draw_board(turn: &Turn) {
            color::Fg(color::Reset))
             .expect("Writing on buffer `board_to_string` failed!");
     match turn.get_state() {
-            Some(side) if side == Side::Dark => {
+            Some(Side::Dark) => {
                 write!(board_to_string,
                        "{}{}{}{}{}   {}{}{}",
                        color::Fg(color::Black),
@@ -367,7 +490,7 @@ pub fn draw_board(turn: &Turn) {
                        LIGHT_DISK,
                        color::Fg(color::Reset))
             }
-            Some(side) if side == Side::Light => {
+            Some(Side::Light) => {
                 write!(board_to_string,
                        "{}{}{}   {}{}{}{}{}",
                        color::Fg(color::Black),
@@ -389,16 +512,32 @@ pub fn draw_board(turn: &Turn) {
                        LIGHT_DISK,
                        color::Fg(color::Reset))
             }
-            _ => unreachable!(),
         }
         .expect("Writing on buffer `board_to_string` failed!");
-    write!(board_to_string,
-           " {}{:<2}       {}{}\n\n",
+    writeln!(board_to_string,
+           " {}{:<2}       {}{}",
            color::Fg(color::LightWhite),
            score_light,
            color::Fg(color::Reset),
            color::Bg(color::Reset))
             .expect("Writing on buffer `board_to_string` failed!");
+    if let Some(clock) = clock {
+        writeln!(board_to_string,
+               "\t{}{}{:>9}      {}{:<9} {}{}",
+               color::Bg(color::LightGreen),
+               color::Fg(color::Black),
+               clock::format_time(clock.get_remaining(Side::Dark)),
+               color::Fg(color::LightWhite),
+               clock::format_time(clock.get_remaining(Side::Light)),
+               color::Fg(color::Reset),
+               color::Bg(color::Reset))
+                .expect("Writing on buffer `board_to_string` failed!");
+    }
+    if let Some(name) = openings::opening_name(turn.get_bit_board()) {
+        writeln!(board_to_string, "\tOpening: {}{}{}", style::Bold, name, style::Reset)
+            .expect("Writing on buffer `board_to_string` failed!");
+    }
+    writeln!(board_to_string).expect("Writing on buffer `board_to_string` failed!");
     board_to_string
         .flush()
         .expect("Flushing buffer `board_to_string` failed!");
@@ -425,6 +564,48 @@ pub fn move_message(side: Side, coord: Coord) {
     }
 }
 
+/// Prints the time spent on a move, warning if it exceeded the budget.
+pub fn time_message(time: f64, budget: Option<f64>) {
+    match budget {
+        Some(budget) if time > budget => {
+            println!("\tTime: {:.3}s, {}over the {:.3}s budget{}!",
+                     time,
+                     style::Bold,
+                     budget,
+                     style::Reset)
+        }
+        _ => println!("\tTime: {:.3}s", time),
+    }
+}
+
+/// Shows how the searches of the custom AI go, one line per completed iteration.
+pub struct SearchDisplay;
+
+impl SearchObserver for SearchDisplay {
+    fn depth_finished(&self, info: &SearchInfo) {
+        let best = match info.best_move() {
+            Some(best) => best,
+            None => return,
+        };
+        let score = match best.score {
+            Score::Running(val) => format!("{:+.2}", val),
+            Score::Ended(diff) => format!("{:+}", diff),
+        };
+        let line: Vec<String> = best.line.iter()
+            .map(|&coord| if coord.get_row() < 8 { record::format_coord(coord) } else { "pass".to_string() })
+            .collect();
+        println!("\t{}{} {:>2}{}: {} {}  ({} nodes, {:.0}k nps)",
+                 style::Bold,
+                 if info.solving { "Empties" } else { "Depth" },
+                 info.depth,
+                 style::Reset,
+                 score,
+                 line.join(" "),
+                 info.nodes,
+                 info.nps() / 1000.0);
+    }
+}
+
 /// Print a message to declare winner
 pub fn endgame_message(winner: Option<Side>) {
     match winner {
@@ -435,6 +616,30 @@ pub fn endgame_message(winner: Option<Side>) {
 
 }
 
+/// Print the outcome of saving or loading a game
+pub fn record_message(result: ::std::result::Result<(), String>, path: &str) {
+    match result {
+        Ok(()) => println!("\tGame saved to {}{}{}.", style::Bold, path, style::Reset),
+        Err(err) => println!("\t{}Error{}: {}.", style::Bold, style::Reset, err),
+    }
+}
+
+/// Print a message when a player runs out of time
+pub fn time_out_message(side: Side) {
+    match side {
+        Side::Dark => println!("\t{}Dark{} ran out of time: {}Light wins{}!",
+                               style::Bold,
+                               style::Reset,
+                               style::Bold,
+                               style::Reset),
+        Side::Light => println!("\t{}Light{} ran out of time: {}Dark wins{}!",
+                                style::Bold,
+                                style::Reset,
+                                style::Bold,
+                                style::Reset),
+    }
+}
+
 /// Print a last message before a player quits the game
 pub fn quitting_message(state: State) {
     match state {
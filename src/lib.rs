@@ -5,24 +5,38 @@
 // External crates
 extern crate rand;
 extern crate rayon;
+#[cfg(feature = "reversi")]
 extern crate reversi;
 extern crate termion;
 extern crate smallvec;
 
 // Modules
+pub mod game;
 pub mod interface;
 pub mod human_player;
 pub mod ai_player;
 pub mod custom_ai;
 pub mod bit_board;
+pub mod transposition;
+pub mod clock;
+pub mod record;
+pub mod ggf;
+pub mod gtp;
+pub mod nboard;
+pub mod selfplay;
+pub mod pattern_eval;
+pub mod book;
+pub mod openings;
+pub mod perft;
 
-use reversi::{ReversiError};
-use reversi::game::{PlayerAction};
+use game::{PlayerAction, ReversiError};
 use std::result;
 
 pub enum OtherAction {
     Help,
     Quit,
+    /// Save the game to the given file.
+    Save(String),
 }
 
 pub type Action = PlayerAction<OtherAction>;
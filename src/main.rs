@@ -10,17 +10,113 @@
 #![cfg_attr(feature="clippy", plugin(clippy))]
 
 extern crate rusthello_lib;
-extern crate reversi;
 
-use reversi::{ReversiError, Side};
-use reversi::game::{PlayerAction, IsPlayer, Game};
 use rusthello_lib::{OtherAction, Result};
-use rusthello_lib::{interface, human_player, ai_player};
+use rusthello_lib::game::{Coord, Game, IsPlayer, PlayerAction, ReversiError, Side, Turn};
+use rusthello_lib::{interface, human_player, book, gtp, nboard};
 use rusthello_lib::interface::{UserCommand};
+use rusthello_lib::ai_player::{AiKind, AiPlayer, TimeControl};
+use rusthello_lib::custom_ai::{Evaluator, RootMove, SearchConfig, SearchInfo, SearchObserver, SilentObserver};
+use rusthello_lib::book::BookMode;
+use rusthello_lib::clock::Clock;
+use rusthello_lib::record::{GameRecord, ReplayPlayer};
+use rusthello_lib::ggf::{self, GgfGame};
+use std::cell::RefCell;
 use std::cmp::Ordering;
-use std::time::{Instant};
+use std::collections::VecDeque;
+use std::env;
+use std::io;
+use std::process;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 fn main() {
+    // Pattern weights for the custom AI
+    let args: Vec<String> = env::args().collect();
+    let mut config = SearchConfig::default();
+    if let Some(n) = args.iter().position(|arg| arg == "--weights") {
+        let loaded = args.get(n + 1)
+            .ok_or_else(|| "--weights needs a file name".to_string())
+            .and_then(|path| Evaluator::load(path));
+        match loaded {
+            Ok(evaluator) => config.evaluator = Arc::new(evaluator),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Threads of the custom AI searches
+    if let Some(n) = args.iter().position(|arg| arg == "--threads") {
+        match args.get(n + 1).and_then(|threads| threads.parse().ok()) {
+            Some(threads) => config.set_threads(threads),
+            None => {
+                eprintln!("Error: --threads needs a number");
+                process::exit(1);
+            }
+        }
+    }
+
+    // Size of the transposition tables of the custom AI searches
+    if let Some(n) = args.iter().position(|arg| arg == "--tt-size") {
+        match args.get(n + 1).and_then(|size| size.parse().ok()) {
+            Some(size) => config.tt_size_mb = size,
+            None => {
+                eprintln!("Error: --tt-size needs a number of megabytes");
+                process::exit(1);
+            }
+        }
+    }
+
+    // Noise at the root of the custom AI searches, for varied games
+    config.noise = args.iter().any(|arg| arg == "--noise");
+
+    // Opening book for the AIs
+    if let Some(n) = args.iter().position(|arg| arg == "--book") {
+        let option = |name: &str| args.iter().position(|arg| arg == name).and_then(|n| args.get(n + 1));
+        let loaded = args.get(n + 1)
+            .ok_or_else(|| "--book needs a file name".to_string())
+            .and_then(|path| {
+                let depth = match option("--book-depth") {
+                    Some(depth) => depth.parse().map_err(|_| "--book-depth needs a number".to_string())?,
+                    None => 60,
+                };
+                let mode = match option("--book-mode") {
+                    Some(mode) => mode.parse()?,
+                    None => BookMode::WeightedRandom,
+                };
+                book::load_book(path, depth, mode)
+            });
+        match loaded {
+            Ok(book) => config.book = Some(Arc::new(book)),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Engine mode, driven by a GUI over stdin/stdout
+    if args.iter().any(|arg| arg == "--gtp") {
+        let stdin = io::stdin();
+        if gtp::run(stdin.lock(), io::stdout(), config).is_err() {
+            panic!("Engine mode ended with an error!");
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--nboard") {
+        let stdin = io::stdin();
+        if nboard::run(stdin.lock(), io::stdout(), config).is_err() {
+            panic!("Engine mode ended with an error!");
+        }
+        return;
+    }
+
+    // Progress of the custom AI searches, silent by default
+    let search_info = args.iter().any(|arg| arg == "--search-info");
+
     // Main intro
     interface::intro();
 
@@ -30,10 +126,23 @@ fn main() {
         match interface::input_main_menu() {
             // Runs the game
             UserCommand::NewGame => {
-                if play_game().is_err() {
+                if play_game(&[], &config, search_info).is_err() {
                     panic!("Match ended with an error!");
                 }
             }
+            // Resumes a saved game
+            UserCommand::LoadGame => {
+                let path = interface::choose_file();
+                match load_game(&path) {
+                    Ok(record) => {
+                        let resume_point = interface::choose_resume_point(&record);
+                        if play_game(&record.moves[..resume_point], &config, search_info).is_err() {
+                            panic!("Match ended with an error!");
+                        }
+                    }
+                    Err(err) => interface::record_message(Err(err), &path),
+                }
+            }
             // Prints help message
             UserCommand::Help => interface::help(),
             // Print credits
@@ -48,60 +157,134 @@ fn main() {
     }
 }
 
-fn play_game() -> Result<()> {
+/// Plays a match, starting after the given moves, the AIs searching with `config`
+/// and showing the progress of their searches with `search_info`.
+fn play_game(opening: &[Coord], config: &SearchConfig, search_info: bool) -> Result<()> {
+    // The evaluation of the last move of the AIs, in disks, if they searched it
+    let last_eval = Arc::new(Mutex::new(None));
+    let ai = |kind| {
+        let mut ai = AiPlayer::new(kind, config.clone());
+        ai.observer = Box::new(EvalKeeper {
+            inner: if search_info { Box::new(interface::SearchDisplay) } else { Box::new(SilentObserver) },
+            evaluator: config.evaluator.clone(),
+            eval: last_eval.clone(),
+        });
+        Box::new(ai) as Box<dyn IsPlayer<OtherAction>>
+    };
 
     // Get the two players
     interface::new_player_menu();
+    let time_control = interface::choose_time_control();
+    let clock = time_control.map(|(initial, increment)| Rc::new(Clock::new(initial, increment)));
+    let time_control = time_control
+        .map_or(String::new(), |(initial, increment)| ggf::format_time_control(initial, increment));
     let mut dark_human = false;
-    let dark = match interface::choose_new_player(Side::Dark) {
+    let mut dark_budget = None;
+    let dark_choice = interface::choose_new_player(Side::Dark);
+    let dark_name = interface::player_name(&dark_choice);
+    let dark = match dark_choice {
         UserCommand::Quit => return Ok(()),
         UserCommand::HumanPlayer => {
             dark_human = true;
-            Box::new(human_player::HumanPlayer) as Box<IsPlayer<OtherAction>>
+            Box::new(human_player::HumanPlayer) as Box<dyn IsPlayer<OtherAction>>
+        }
+        UserCommand::AiWeak => ai(AiKind::Weak),
+        UserCommand::AiMedium => ai(AiKind::Medium),
+        UserCommand::AiStrong => ai(AiKind::Strong),
+        UserCommand::AiCustom => ai(AiKind::Custom),
+        UserCommand::AiTimed => {
+            let time_control = match clock {
+                Some(ref clock) => TimeControl::Clock(clock.clone()),
+                None => {
+                    let time = interface::choose_time_per_move();
+                    dark_budget = Some(duration_to_secs(time));
+                    TimeControl::PerMove(time)
+                }
+            };
+            ai(AiKind::Timed(time_control))
         }
-        UserCommand::AiWeak => Box::new(ai_player::AiPlayer::Weak) as Box<IsPlayer<OtherAction>>,
-        UserCommand::AiMedium => Box::new(ai_player::AiPlayer::Medium) as Box<IsPlayer<OtherAction>>,
-        UserCommand::AiStrong => Box::new(ai_player::AiPlayer::Strong) as Box<IsPlayer<OtherAction>>,
-        UserCommand::AiCustom => Box::new(ai_player::AiPlayer::Custom) as Box<IsPlayer<OtherAction>>,
         _ => panic!("Returned an invalid player choice"),
     };
     let mut light_human = false;
-    let light = match interface::choose_new_player(Side::Light) {
+    let mut light_budget = None;
+    let light_choice = interface::choose_new_player(Side::Light);
+    let light_name = interface::player_name(&light_choice);
+    let light = match light_choice {
         UserCommand::Quit => return Ok(()),
         UserCommand::HumanPlayer => {
             light_human = true;
-            Box::new(human_player::HumanPlayer) as Box<IsPlayer<OtherAction>>
+            Box::new(human_player::HumanPlayer) as Box<dyn IsPlayer<OtherAction>>
+        }
+        UserCommand::AiWeak => ai(AiKind::Weak),
+        UserCommand::AiMedium => ai(AiKind::Medium),
+        UserCommand::AiStrong => ai(AiKind::Strong),
+        UserCommand::AiCustom => ai(AiKind::Custom),
+        UserCommand::AiTimed => {
+            let time_control = match clock {
+                Some(ref clock) => TimeControl::Clock(clock.clone()),
+                None => {
+                    let time = interface::choose_time_per_move();
+                    light_budget = Some(duration_to_secs(time));
+                    TimeControl::PerMove(time)
+                }
+            };
+            ai(AiKind::Timed(time_control))
         }
-        UserCommand::AiWeak => Box::new(ai_player::AiPlayer::Weak) as Box<IsPlayer<OtherAction>>,
-        UserCommand::AiMedium => Box::new(ai_player::AiPlayer::Medium) as Box<IsPlayer<OtherAction>>,
-        UserCommand::AiStrong => Box::new(ai_player::AiPlayer::Strong) as Box<IsPlayer<OtherAction>>,
-        UserCommand::AiCustom => Box::new(ai_player::AiPlayer::Custom) as Box<IsPlayer<OtherAction>>,
         _ => panic!("Returned an invalid player choice"),
     };
 
     // Print commands info
     interface::commands_info();
 
-    // Create a new game
-    let mut game = Game::new(&*dark, &*light);
+    // Create a new game, replaying the opening moves first
+    let replay = RefCell::new(opening.iter().cloned().collect::<VecDeque<_>>());
+    let replay_dark = ReplayPlayer::new(&replay, &*dark);
+    let replay_light = ReplayPlayer::new(&replay, &*light);
+    let mut game = Game::new(&replay_dark, &replay_light);
+    let clock_ref = clock.as_deref();
+    let mut record = GameRecord::new(dark_name, light_name);
+    // Seconds spent on each move of the record, unknown for the replayed ones
+    let mut times = Vec::new();
+    while !replay.borrow().is_empty() {
+        if let PlayerAction::Move(coord) = game.play_turn()? {
+            record.moves.push(coord);
+            record.evals.push(None);
+            times.push(None);
+        }
+    }
 
     // Draw the current board and game info
-    interface::draw_board(game.get_current_turn());
+    interface::draw_board(game.get_current_turn(), clock_ref);
 
-    // Proceed with turn after turn till the game ends
+    // Proceed with turn after turn till the game ends, or a player runs out of time
     let mut dark_time = 0.0;
     let mut light_time = 0.0;
+    let mut time_loser = None;
     while !game.is_endgame() {
         let state_side = game.get_current_state().unwrap();
         let start = Instant::now();
         let res = game.play_turn();
-        let end = start.elapsed();
-        let end = end.as_secs() as f64 +
-            end.subsec_nanos() as f64 * 1e-9;
+        let elapsed = start.elapsed();
+        let eval = last_eval.lock().unwrap().take();
+        let end = duration_to_secs(elapsed);
+        // Human input is read without a timeout, so that a human player who runs
+        // out of time loses only on answering
+        if let Some(clock) = clock_ref {
+            let moved = matches!(res, Ok(PlayerAction::Move(_)));
+            if !clock.charge(state_side, elapsed, moved) {
+                interface::draw_board(game.get_current_turn(), clock_ref);
+                interface::time_out_message(state_side);
+                time_loser = Some(state_side);
+                break;
+            }
+        }
         match res {
             Ok(action) => {
                 match action {
                     PlayerAction::Move(coord) => {
+                        record.moves.push(coord);
+                        record.evals.push(eval);
+                        times.push(Some(end));
                         match state_side {
                             Side::Dark => {
                                 dark_time += end;
@@ -116,13 +299,24 @@ fn play_game() -> Result<()> {
                                 }
                             }
                         }
-                        println!("Time: {}s", end);
-                        interface::draw_board(game.get_current_turn());
+                        interface::time_message(end, match state_side {
+                            Side::Dark => dark_budget,
+                            Side::Light => light_budget,
+                        });
+                        interface::draw_board(game.get_current_turn(), clock_ref);
+                    }
+                    PlayerAction::Undo => {
+                        undo_record(&mut record, game.get_current_turn());
+                        times.truncate(record.moves.len());
+                        interface::draw_board(game.get_current_turn(), clock_ref);
                     }
-                    PlayerAction::Undo => interface::draw_board(game.get_current_turn()),
                     PlayerAction::Other(OtherAction::Help) => {
                         interface::help();
-                        interface::draw_board(game.get_current_turn());
+                        interface::draw_board(game.get_current_turn(), clock_ref);
+                    }
+                    PlayerAction::Other(OtherAction::Save(path)) => {
+                        record.set_opening();
+                        interface::record_message(save_game(&record, &times, &time_control, &path), &path);
                     }
                     PlayerAction::Other(OtherAction::Quit) => {
                         interface::quitting_message(game.get_current_state());
@@ -139,13 +333,82 @@ fn play_game() -> Result<()> {
         }
     }
 
-    let (score_dark, score_light) = game.get_current_turn().get_score();
-    interface::endgame_message(match score_dark.cmp(&score_light) {
-                                   Ordering::Greater => Some(Side::Dark),
-                                   Ordering::Less => Some(Side::Light),
-                                   Ordering::Equal => None,
-    });
+    match time_loser {
+        Some(loser) => record.set_time_loss(loser),
+        None => {
+            let (score_dark, score_light) = game.get_current_turn().get_score();
+            interface::endgame_message(match score_dark.cmp(&score_light) {
+                                           Ordering::Greater => Some(Side::Dark),
+                                           Ordering::Less => Some(Side::Light),
+                                           Ordering::Equal => None,
+            });
+            record.set_result(score_dark, score_light);
+        }
+    }
     println!("Dark: {}s, Light: {}s", dark_time, light_time);
 
+    record.set_opening();
+    if let Some(path) = interface::ask_save_file() {
+        interface::record_message(save_game(&record, &times, &time_control, &path), &path);
+    }
+
     Ok(())
 }
+
+/// Saves a game as GGF if the file name ends with `.ggf`, as a transcript otherwise.
+fn save_game(record: &GameRecord, times: &[Option<f64>], time_control: &str, path: &str)
+             -> ::std::result::Result<(), String> {
+    if path.to_lowercase().ends_with(".ggf") {
+        GgfGame::from_record(record, times, time_control)?.save(path)
+    } else {
+        record.save(path)
+    }
+}
+
+/// Loads a game saved by `save_game`.
+fn load_game(path: &str) -> ::std::result::Result<GameRecord, String> {
+    if path.to_lowercase().ends_with(".ggf") {
+        GgfGame::load(path)?.to_record()
+    } else {
+        GameRecord::load(path)
+    }
+}
+
+/// Drops the moves taken back by an undo from the record.
+fn undo_record(record: &mut GameRecord, turn: &Turn) {
+    let board = turn.get_bit_board();
+    if let Ok(positions) = record.positions() {
+        if let Some(n) = positions.iter().rposition(|&position| position == board) {
+            record.moves.truncate(n);
+            record.evals.truncate(n);
+        }
+    }
+}
+
+/// Keeps the evaluation of the last search of an AI, in disks, passing everything on to `inner`.
+struct EvalKeeper {
+    inner: Box<dyn SearchObserver>,
+    evaluator: Arc<Evaluator>,
+    eval: Arc<Mutex<Option<f64>>>,
+}
+
+impl SearchObserver for EvalKeeper {
+    fn depth_started(&self, depth: usize, solving: bool) {
+        self.inner.depth_started(depth, solving);
+    }
+
+    fn new_best_move(&self, depth: usize, solving: bool, best: &RootMove) {
+        self.inner.new_best_move(depth, solving, best);
+    }
+
+    fn depth_finished(&self, info: &SearchInfo) {
+        if let Some(best) = info.best_move() {
+            *self.eval.lock().unwrap() = Some(self.evaluator.score_disks(best.score));
+        }
+        self.inner.depth_finished(info);
+    }
+}
+
+fn duration_to_secs(time: Duration) -> f64 {
+    time.as_secs() as f64 + time.subsec_nanos() as f64 * 1e-9
+}
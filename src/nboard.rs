@@ -0,0 +1,289 @@
+//! An engine mode speaking the protocol of the NBoard GUI (version 2).
+//!
+//! The GUI sends the game with `set game <ggf>` and the moves played after it with
+//! `move <mv>`; `go` asks for a move, answered with `=== <mv>/eval/time`, and
+//! `hint n` for the best `n` moves, answered with `search <pv> <eval> 0 <depth>` lines
+//! which improve as the search deepens. Evaluations are in disks, and moves are
+//! timed by the clock of the game, if it has one.
+
+use game::Coord;
+use bit_board;
+use bit_board::BitBoard;
+use custom_ai;
+use custom_ai::{Deadline, SearchConfig, SearchContext, SilentObserver};
+use ggf;
+use ggf::{GgfGame, GgfMove};
+use record;
+use transposition::TranspositionTable;
+use std::io;
+use std::io::{BufRead, Write};
+use std::time::Instant;
+
+/// A move with its evaluation (in disks, for the side to move) and the line expected after it.
+type Line = (Coord, f64, Vec<Coord>);
+
+pub struct Engine {
+    game: GgfGame,
+    /// The midgame search depth.
+    depth: usize,
+    /// How the positions are searched.
+    config: SearchConfig,
+    tt: TranspositionTable,
+}
+
+impl Engine {
+    /// An engine searching with `config`.
+    pub fn new(config: SearchConfig) -> Engine {
+        Engine {
+            game: new_game(),
+            depth: custom_ai::USUAL_DEPTH,
+            tt: config.new_table(),
+            config,
+        }
+    }
+
+    /// The current position, if the game is valid.
+    fn board(&self) -> Option<BitBoard> {
+        self.game.positions().ok().and_then(|positions| positions.last().cloned())
+    }
+
+    /// Executes a command, writing the replies to `output`.
+    /// Returns `false` if the engine has to quit.
+    pub fn execute<W: Write>(&mut self, line: &str, output: &mut W) -> io::Result<bool> {
+        let mut words = line.splitn(2, ' ');
+        let command = words.next().unwrap_or("");
+        let args = words.next().unwrap_or("").trim();
+        match command {
+            "nboard" => writeln!(output, "set myname RUSThello")?,
+            "set" => {
+                let mut words = args.splitn(2, ' ');
+                match (words.next(), words.next()) {
+                    (Some("depth"), Some(depth)) => {
+                        if let Ok(depth) = depth.trim().parse::<usize>() {
+                            self.depth = ::std::cmp::max(depth, 1);
+                        }
+                    }
+                    (Some("game"), Some(game)) => {
+                        match game.parse::<GgfGame>() {
+                            Ok(game) => {
+                                self.game = game;
+                                self.tt.clear();
+                            }
+                            Err(err) => writeln!(output, "status Invalid game: {}", err)?,
+                        }
+                    }
+                    // Contempt and other settings are not supported
+                    _ => (),
+                }
+            }
+            "move" => {
+                match ggf::parse_move(args) {
+                    Ok(mv) => {
+                        self.game.moves.push(mv);
+                        if self.game.positions().is_err() {
+                            self.game.moves.pop();
+                            writeln!(output, "status Illegal move {}", args)?;
+                        }
+                    }
+                    Err(err) => writeln!(output, "status Invalid move: {}", err)?,
+                }
+            }
+            "go" => self.go(output)?,
+            "hint" => {
+                let hints = args.parse::<usize>().unwrap_or(1);
+                self.hint(hints, output)?;
+            }
+            "ping" => writeln!(output, "pong {}", args)?,
+            "learn" => writeln!(output, "learned")?,
+            "quit" => return Ok(false),
+            // Unknown commands are ignored, as the protocol requires
+            _ => (),
+        }
+        output.flush()?;
+        Ok(true)
+    }
+
+    fn go<W: Write>(&mut self, output: &mut W) -> io::Result<()> {
+        let board = match self.board() {
+            Some(board) => board,
+            None => return writeln!(output, "status Invalid game"),
+        };
+        let start = Instant::now();
+        let deadline = match self.game.clock() {
+            Some((remaining, increment)) => {
+                Deadline::after(custom_ai::time_for_move(remaining, increment, board.empties() as usize, &self.config))
+            }
+            None => Deadline::never(),
+        };
+        writeln!(output, "status Thinking")?;
+        let lines = search(board, self.depth, false, &self.config, &self.tt, &deadline, |depth, lines| {
+            if let Some(&(_, eval, ref pv)) = lines.first() {
+                writeln!(output, "status Depth {}: {} {:.2}", depth, format_line(pv), eval)?;
+                output.flush()?;
+            }
+            Ok(())
+        })?;
+        let elapsed = start.elapsed();
+        let mv = GgfMove {
+            coord: lines.first().map(|&(coord, _, _)| coord),
+            eval: lines.first().map(|&(_, eval, _)| eval),
+            time: Some(elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9),
+        };
+        writeln!(output, "=== {}", mv)?;
+        writeln!(output, "status")
+    }
+
+    fn hint<W: Write>(&mut self, hints: usize, output: &mut W) -> io::Result<()> {
+        let board = match self.board() {
+            Some(board) => board,
+            None => return writeln!(output, "status Invalid game"),
+        };
+        writeln!(output, "status Analyzing")?;
+        // The evaluations of the analysis are given as they are, without noise
+        let mut config = self.config.clone();
+        config.noise = false;
+        search(board, self.depth, true, &config, &self.tt, &Deadline::never(), |depth, lines| {
+            for &(_, eval, ref pv) in lines.iter().take(hints) {
+                writeln!(output, "search {} {:.2} 0 {}", format_line(pv), eval, depth)?;
+            }
+            output.flush()
+        })?;
+        writeln!(output, "status")
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Engine {
+        Engine::new(SearchConfig::default())
+    }
+}
+
+/// Runs the engine, reading commands from `input` until `quit` or the end of the input.
+/// The positions are searched with `config`.
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W, config: SearchConfig) -> io::Result<()> {
+    let mut engine = Engine::new(config);
+    for line in input.lines() {
+        if !engine.execute(line?.trim(), &mut output)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Searches `board` deeper and deeper, up to `max_depth` in the midgame or
+/// till the end of the game, calling `report` after each iteration with the
+/// moves found so far, best first. With `all`, every move gets an exact
+/// evaluation, and not only the best one.
+/// Once `deadline` expires, the moves of the last completed iteration are returned;
+/// the first iteration is always completed.
+fn search<F>(board: BitBoard,
+             max_depth: usize,
+             all: bool,
+             config: &SearchConfig,
+             tt: &TranspositionTable,
+             deadline: &Deadline,
+             mut report: F)
+             -> io::Result<Vec<Line>>
+    where F: FnMut(&str, &[Line]) -> io::Result<()>
+{
+    let (my, opp) = (board.mover(), board.opponent());
+    let moves = bit_board::valid_moves_set(my, opp);
+    if moves == 0 {
+        return Ok(Vec::new());
+    }
+    let left = (64 - bit_board::get_tempo(my, opp)) as usize;
+    let solving = left <= config.endgame_length();
+    tt.new_search();
+    let never = Deadline::never();
+    let first = SearchContext { config, tt, deadline: &never, observer: &SilentObserver };
+    let ctx = SearchContext { deadline, ..first };
+    // Either all the moves together, or each one on its own for exact scores
+    let mut groups = Vec::new();
+    if all {
+        let mut rest = moves;
+        while rest != 0 {
+            let disk = 1u64 << rest.trailing_zeros();
+            rest ^= disk;
+            groups.push(disk);
+        }
+    } else {
+        groups.push(moves);
+    }
+    let mut lines = Vec::new();
+    if solving && deadline.remaining().is_some() {
+        // A quick search to fall back on if the solver runs out of time
+        lines = iteration(board, &groups, 1, false, &first).expect("The first iteration has no deadline");
+    }
+    let mut depth = 1;
+    while depth <= max_depth || solving {
+        let ctx = if lines.is_empty() { &first } else { &ctx };
+        lines = match iteration(board, &groups, depth, solving, ctx) {
+            Some(found) => found,
+            None => break,
+        };
+        if solving {
+            report("100%", &lines)?;
+            break;
+        }
+        report(&depth.to_string(), &lines)?;
+        depth += 1;
+    }
+    Ok(lines)
+}
+
+/// Searches each group of moves of `board` to `depth`, or till the end of the game,
+/// returning the moves best first, or `None` if the deadline expires.
+fn iteration(board: BitBoard, groups: &[u64], depth: usize, solving: bool, ctx: &SearchContext)
+             -> Option<Vec<Line>> {
+    let (my, opp) = (board.mover(), board.opponent());
+    let mut moves_and_scores = Vec::new();
+    let mut nnodes = 0;
+    for &group in groups.iter() {
+        let mut group_scores = Vec::new();
+        let completed = if solving {
+            custom_ai::ai_eval_till_end(my, opp, group, &mut group_scores, false, ctx, &mut nnodes)
+        } else {
+            custom_ai::ai_eval_with_depth(my, opp, depth, group, &mut group_scores, ctx, &mut nnodes)
+        };
+        if !completed {
+            return None;
+        }
+        moves_and_scores.extend(group_scores);
+    }
+    moves_and_scores.sort_by_key(|&(_, score)| score);
+    let lines = moves_and_scores.into_iter()
+        .map(|(coord, score)| {
+            let mut pv = vec![coord];
+            if let Some(next) = record::play(board, coord) {
+                pv.extend(custom_ai::principal_variation(next, ctx.tt));
+            }
+            // Entries left by deeper searches could make the line longer than the search
+            if !solving {
+                pv.truncate(depth);
+            }
+            // Scores are from the opponent's point of view, and midgame ones are turned into disks
+            let eval = -ctx.config.evaluator.score_disks(score);
+            (coord, eval, pv)
+        })
+        .collect();
+    Some(lines)
+}
+
+fn format_line(line: &[Coord]) -> String {
+    line.iter()
+        .map(|&coord| if coord.get_row() >= 8 { "PA".to_string() } else { record::format_coord(coord).to_uppercase() })
+        .collect()
+}
+
+fn new_game() -> GgfGame {
+    GgfGame {
+        place: String::new(),
+        date: String::new(),
+        black: String::new(),
+        white: String::new(),
+        time_control: String::new(),
+        result: "?".to_string(),
+        start: bit_board::START,
+        moves: Vec::new(),
+    }
+}
@@ -0,0 +1,84 @@
+//! Names of the usual openings, recognised in any of the 8 orientations of the board.
+
+use game::Coord;
+use bit_board;
+use bit_board::BitBoard;
+use record::{self, GameRecord};
+use std::collections::HashMap;
+
+/// The named openings, as transcripts from the usual starting position.
+const OPENINGS: &[(&str, &str)] = &[
+    ("Diagonal", "f5f6"),
+    ("Perpendicular", "f5d6"),
+    ("Parallel", "f5f4"),
+    ("Heath", "f5f6e6f4g5"),
+    ("Tiger", "f5d6c3d3c4"),
+    ("Brightwell", "f5d6c3d3c4f4c6"),
+    ("Aubrey", "f5d6c3d3c4f4f6g5"),
+    ("Stephenson", "f5d6c3d3c4f4c5b3c2"),
+    ("Aircraft", "f5d6c3d3c4f4c5b3c2b4"),
+    ("No-Kung", "f5d6c3d3c4f4c5b3c2e3d2c6b4a4"),
+    ("Ishii", "f5d6c3d3c4f4f6f3e6e7"),
+    ("Mainline Tiger", "f5d6c3d3c4f4f6f3e6e7d7"),
+    ("Rose-v-Toth", "f5d6c3d3c4f4f6f3e6e7c6"),
+    ("Tamenori", "f5d6c3d3c4f4f6f3e6e7f7"),
+    ("Cow", "f5d6c5"),
+    ("Rose-bill", "f5d6c5f4e3f6g5"),
+    ("Rose", "f5d6c5f4e3f6g5e6e7"),
+];
+
+/// The name of the opening being played in a position, if it is on the line of a named
+/// opening: the longest of the named openings played to reach it.
+pub fn opening_name(board: BitBoard) -> Option<&'static str> {
+    lines().get(&key(board)).cloned().unwrap_or(None)
+}
+
+/// The name of the longest named opening played in a game, if any.
+pub fn game_opening(moves: &[Coord]) -> Option<&'static str> {
+    let lines = lines();
+    let mut name = None;
+    let mut board = bit_board::START;
+    for &coord in moves.iter() {
+        board = match record::play(board, coord) {
+            Some(board) => board,
+            None => break,
+        };
+        match lines.get(&key(board)) {
+            Some(&Some(longer)) => name = Some(longer),
+            Some(&None) => (),
+            // The game has left the named lines
+            None => break,
+        }
+    }
+    name
+}
+
+/// The positions on the lines of the named openings, with their names.
+fn lines() -> HashMap<(u64, u64, bool), Option<&'static str>> {
+    let positions: Vec<(&'static str, Vec<BitBoard>)> = OPENINGS.iter()
+        .map(|&(name, transcript)| {
+            let record: GameRecord = transcript.parse().expect("Named openings are valid transcripts");
+            (name, record.positions().expect("Named openings are legal"))
+        })
+        .collect();
+    let ends: HashMap<_, _> = positions.iter()
+        .map(|&(name, ref boards)| (key(*boards.last().expect("There is the starting position")), name))
+        .collect();
+    let mut lines = HashMap::new();
+    for (_, boards) in positions.iter() {
+        let mut name = None;
+        for &board in boards.iter() {
+            name = ends.get(&key(board)).cloned().or(name);
+            let entry = lines.entry(key(board)).or_insert(None);
+            if entry.is_none() {
+                *entry = name;
+            }
+        }
+    }
+    lines
+}
+
+fn key(board: BitBoard) -> (u64, u64, bool) {
+    let (bl, wh, _) = bit_board::canonical(board.black(), board.white());
+    (bl, wh, board.black_to_move())
+}
@@ -0,0 +1,213 @@
+//! A pattern-based evaluation, in the style of Logistello and Edax.
+//!
+//! The board is read through a few patterns (edges with the X-squares, 3x3 and 2x5
+//! corners, diagonals), each looked at in all its symmetric placements. Every
+//! configuration of a pattern has its own weight, for each phase of the game,
+//! and the evaluation is the sum of the weights of the configurations on the board,
+//! plus a weight for the mobility difference and a constant term,
+//! as an estimate of the final disk difference for the side to move.
+//!
+//! Weights are saved as a binary file: the magic bytes `RTPW`, then the version,
+//! the number of phases and the number of weights per phase (as little-endian `u32`),
+//! then all the weights (as little-endian `f32`), phase after phase.
+//! Version 2 added the mobility weight before the constant term of each phase;
+//! files of version 1 are still read, with no weight for the mobility.
+
+use bit_board;
+use bit_board::SYMMETRIES;
+use std::fs::File;
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 4] = b"RTPW";
+const VERSION: u32 = 2;
+/// Number of phases of the game, by the number of disks on the board.
+pub const PHASES: usize = 6;
+
+/// The patterns, as the squares (`row * 8 + col`) they are made of in their base placement,
+/// and how many of their symmetric placements (the first ones of `SYMMETRIES`) are distinct.
+const PATTERNS: [(&[u8], usize); 8] = [
+    // Edge plus the two X-squares
+    (&[0, 1, 2, 3, 4, 5, 6, 7, 9, 14], 4),
+    // 3x3 corner
+    (&[0, 1, 2, 8, 9, 10, 16, 17, 18], 4),
+    // 2x5 corner
+    (&[0, 1, 2, 3, 4, 8, 9, 10, 11, 12], 8),
+    // Diagonals, from the longest
+    (&[0, 9, 18, 27, 36, 45, 54, 63], 2),
+    (&[1, 10, 19, 28, 37, 46, 55], 4),
+    (&[2, 11, 20, 29, 38, 47], 4),
+    (&[3, 12, 21, 30, 39], 4),
+    (&[4, 13, 22, 31], 4),
+];
+
+/// A position, from the side to move, and its final disk difference.
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub my: u64,
+    pub opp: u64,
+    pub diff: f32,
+}
+
+/// A placement of a pattern on the board.
+#[derive(Clone)]
+struct Instance {
+    /// Where the weights of the pattern start, in the weights of a phase.
+    offset: usize,
+    squares: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct PatternWeights {
+    instances: Vec<Instance>,
+    /// The number of weights of each phase, the last two being those of
+    /// the mobility difference and of the constant term.
+    phase_len: usize,
+    weights: Vec<f32>,
+}
+
+impl PatternWeights {
+    /// All weights set to zero.
+    pub fn zeros() -> PatternWeights {
+        let mut instances = Vec::new();
+        let mut offset = 0;
+        for &(squares, placements) in PATTERNS.iter() {
+            for &symmetry in SYMMETRIES[.. placements].iter() {
+                instances.push(Instance {
+                    offset,
+                    squares: squares.iter()
+                        .map(|&square| symmetry.apply(1u64 << square).trailing_zeros() as u8)
+                        .collect(),
+                });
+            }
+            offset += 3usize.pow(squares.len() as u32);
+        }
+        let phase_len = offset + 2;
+        PatternWeights {
+            instances,
+            phase_len,
+            weights: vec![0.0; phase_len * PHASES],
+        }
+    }
+
+    pub fn load(path: &str) -> Result<PatternWeights, String> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|err| format!("cannot read {}: {}", path, err))?;
+        let mut weights = PatternWeights::zeros();
+        let header = 4 + 3 * 4;
+        if bytes.len() < header || &bytes[0 .. 4] != MAGIC {
+            return Err(format!("{} is not a weight file", path));
+        }
+        let version = read_u32(&bytes[4 ..]);
+        // Version 1 has no mobility weight
+        let file_phase_len = if version == 1 { weights.phase_len - 1 } else { weights.phase_len };
+        if (version != 1 && version != VERSION) || read_u32(&bytes[8 ..]) as usize != PHASES ||
+           read_u32(&bytes[12 ..]) as usize != file_phase_len {
+            return Err(format!("{} has weights for different patterns", path));
+        }
+        if bytes.len() != header + 4 * PHASES * file_phase_len {
+            return Err(format!("{} has the wrong length", path));
+        }
+        let phase_len = weights.phase_len;
+        for (n, chunk) in bytes[header ..].chunks(4).enumerate() {
+            let (phase, mut index) = (n / file_phase_len, n % file_phase_len);
+            if index == file_phase_len - 1 {
+                index = phase_len - 1;
+            }
+            weights.weights[phase * phase_len + index] = f32::from_bits(read_u32(chunk));
+        }
+        Ok(weights)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut bytes = Vec::with_capacity(16 + 4 * self.weights.len());
+        bytes.extend_from_slice(MAGIC);
+        for &value in [VERSION, PHASES as u32, self.phase_len as u32].iter() {
+            write_u32(&mut bytes, value);
+        }
+        for &weight in self.weights.iter() {
+            write_u32(&mut bytes, weight.to_bits());
+        }
+        File::create(path)
+            .and_then(|mut file| file.write_all(&bytes))
+            .map_err(|err| format!("cannot write {}: {}", path, err))
+    }
+
+    /// All the weights, phase after phase.
+    pub fn weights_mut(&mut self) -> &mut [f32] {
+        &mut self.weights
+    }
+
+    /// The features of a position, as the indices (in `weights_mut`) of their weights
+    /// and their values: the evaluation is the sum of the weights times the values.
+    pub fn features(&self, my: u64, opp: u64, features: &mut Vec<(usize, f32)>) {
+        let base = phase(my, opp) * self.phase_len;
+        features.clear();
+        for instance in self.instances.iter() {
+            features.push((base + instance.offset + instance.index(my, opp), 1.0));
+        }
+        features.push((base + self.phase_len - 2, mobility(my, opp)));
+        features.push((base + self.phase_len - 1, 1.0));
+    }
+
+    /// A pass of stochastic gradient descent on the squared error of the evaluations
+    /// of `samples`, in their order. Returns the root mean square error met on the way.
+    pub fn descend(&mut self, samples: &[Sample], rate: f32) -> f64 {
+        let mut features = Vec::new();
+        let mut squared_error = 0.0;
+        for sample in samples.iter() {
+            self.features(sample.my, sample.opp, &mut features);
+            let error = self.eval(sample.my, sample.opp) as f32 - sample.diff;
+            squared_error += (error * error) as f64;
+            for &(index, value) in features.iter() {
+                self.weights[index] -= rate * error * value;
+            }
+        }
+        (squared_error / samples.len().max(1) as f64).sqrt()
+    }
+
+    /// The evaluation of a position, for the side to move (`my`).
+    pub fn eval(&self, my: u64, opp: u64) -> f64 {
+        let base = phase(my, opp) * self.phase_len;
+        let weights = &self.weights[base .. base + self.phase_len];
+        let mut val = weights[self.phase_len - 1] as f64 +
+                      weights[self.phase_len - 2] as f64 * mobility(my, opp) as f64;
+        for instance in self.instances.iter() {
+            val += weights[instance.offset + instance.index(my, opp)] as f64;
+        }
+        val
+    }
+}
+
+impl Instance {
+    /// The configuration of the pattern, as a number in base 3.
+    fn index(&self, my: u64, opp: u64) -> usize {
+        let mut index = 0;
+        for &square in self.squares.iter() {
+            let disk = 1u64 << square;
+            index = index * 3 + if my & disk != 0 { 1 } else if opp & disk != 0 { 2 } else { 0 };
+        }
+        index
+    }
+}
+
+fn mobility(my: u64, opp: u64) -> f32 {
+    bit_board::valid_moves_set(my, opp).count_ones() as f32 - bit_board::valid_moves_set(opp, my).count_ones() as f32
+}
+
+/// The phase of the game, from 0 to `PHASES - 1`.
+pub fn phase(my: u64, opp: u64) -> usize {
+    let disks = (my | opp).count_ones() as usize;
+    ::std::cmp::min(disks.saturating_sub(4) * PHASES / 60, PHASES - 1)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    for shift in [0, 8, 16, 24].iter() {
+        bytes.push((value >> shift) as u8);
+    }
+}
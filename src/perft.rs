@@ -0,0 +1,108 @@
+//! Perft: counts the leaves of the game tree to a given depth, to check move generation.
+//!
+//! A pass counts as a move, and a finished game as a leaf, whatever the depth left.
+
+use {Result};
+use game::Turn;
+use bit_board;
+use bit_board::BitBoard;
+
+/// The known counts from the starting position, by depth.
+pub const REFERENCE: [u64; 15] = [1,
+                                  4,
+                                  12,
+                                  56,
+                                  244,
+                                  1396,
+                                  8200,
+                                  55092,
+                                  390216,
+                                  3005288,
+                                  24571284,
+                                  212258800,
+                                  1939886636,
+                                  18429641748,
+                                  184042084512];
+
+/// Counts the leaves with the move generator of `bit_board`.
+pub fn perft_bit_board(board: BitBoard, depth: usize) -> u64 {
+    perft_my_opp(board.mover(), board.opponent(), depth)
+}
+
+fn perft_my_opp(my: u64, opp: u64, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut moves = bit_board::valid_moves_set(my, opp);
+    if moves == 0 {
+        return if bit_board::valid_moves_set(opp, my) == 0 {
+            1
+        } else {
+            perft_my_opp(opp, my, depth - 1)
+        };
+    }
+    if depth == 1 {
+        return moves.count_ones() as u64;
+    }
+    let mut count = 0;
+    while moves != 0 {
+        let disk = 1u64 << moves.trailing_zeros();
+        moves ^= disk;
+        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+        count += perft_my_opp(nopp, nmy, depth - 1);
+    }
+    count
+}
+
+/// Counts the leaves with the game model of `game`, where passes are implicit:
+/// the same side moves again.
+pub fn perft_turn(turn: &Turn, depth: usize) -> Result<u64> {
+    let side = match turn.get_state() {
+        Some(side) => side,
+        None => return Ok(1),
+    };
+    if depth == 0 {
+        return Ok(1);
+    }
+    let mut count = 0;
+    for coord in turn.get_moves() {
+        let mut next = *turn;
+        next.make_move(coord)?;
+        count += if next.get_state() == Some(side) && depth > 1 {
+            // The opponent passes
+            perft_turn(&next, depth - 2)?
+        } else {
+            perft_turn(&next, depth - 1)?
+        };
+    }
+    Ok(count)
+}
+
+/// Counts the leaves with the move generator of the `reversi` library, as `perft_turn`.
+#[cfg(feature = "reversi")]
+pub fn perft_reversi(turn: &reversi::turn::Turn, depth: usize) -> reversi::Result<u64> {
+    let side = match turn.get_state() {
+        Some(side) => side,
+        None => return Ok(1),
+    };
+    if depth == 0 {
+        return Ok(1);
+    }
+    let mut count = 0;
+    for row in 0 .. reversi::board::BOARD_SIZE {
+        for col in 0 .. reversi::board::BOARD_SIZE {
+            let coord = reversi::board::Coord::new(row, col);
+            if turn.check_move(coord).is_err() {
+                continue;
+            }
+            let mut next = *turn;
+            next.make_move(coord)?;
+            count += if next.get_state() == Some(side) && depth > 1 {
+                perft_reversi(&next, depth - 2)?
+            } else {
+                perft_reversi(&next, depth - 1)?
+            };
+        }
+    }
+    Ok(count)
+}
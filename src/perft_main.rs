@@ -0,0 +1,102 @@
+//! Counts the leaves of the game tree and checks them against the known counts.
+
+#![crate_name = "perft"]
+#![crate_type = "bin"]
+#![cfg_attr(feature="clippy", feature(plugin))]
+#![cfg_attr(feature="clippy", plugin(clippy))]
+
+extern crate rusthello_lib;
+#[cfg(feature = "reversi")]
+extern crate reversi;
+
+use rusthello_lib::bit_board;
+use rusthello_lib::game::Turn;
+use rusthello_lib::perft::{self, REFERENCE};
+use std::env;
+use std::process;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: perft [options] [DEPTH]
+
+Counts the leaves of the game tree from the starting position, at every depth up to DEPTH
+(default 9), and compares them to the known counts. Exits with an error on any difference.
+
+Options:
+    --turn           also count with the game model of the program
+    --reversi        also count with the move generator of the `reversi` library
+                     (if built with the `reversi` feature)";
+
+#[cfg(feature = "reversi")]
+fn reversi_count(depth: usize) -> Option<u64> {
+    Some(perft::perft_reversi(&reversi::turn::Turn::first_turn(), depth).expect("Legal moves can be played"))
+}
+
+#[cfg(not(feature = "reversi"))]
+fn reversi_count(_depth: usize) -> Option<u64> {
+    None
+}
+
+fn main() {
+    let mut depth = 9;
+    let mut with_turn = false;
+    let mut with_reversi = false;
+    for arg in env::args().skip(1) {
+        match &*arg {
+            "--turn" => with_turn = true,
+            "--reversi" => with_reversi = true,
+            _ => {
+                depth = match arg.parse() {
+                    Ok(depth) => depth,
+                    Err(_) => {
+                        eprintln!("{}", USAGE);
+                        process::exit(1);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut ok = true;
+    println!("{:>5} {:>15} {:>15} {:>9}", "Depth", "Leaves", "Expected", "Seconds");
+    for depth in 1 .. depth + 1 {
+        let start = Instant::now();
+        let count = perft::perft_bit_board(bit_board::START, depth);
+        let elapsed = start.elapsed();
+        let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+        let expected = REFERENCE.get(depth).cloned();
+        print!("{:>5} {:>15} {:>15} {:>9.3}",
+               depth,
+               count,
+               expected.map_or("?".to_string(), |expected| expected.to_string()),
+               seconds);
+        if expected.is_some_and(|expected| expected != count) {
+            print!("  MISMATCH");
+            ok = false;
+        }
+        if with_turn {
+            let turn_count = perft::perft_turn(&Turn::first_turn(), depth)
+                .expect("Legal moves can be played");
+            if turn_count != count {
+                print!("  game counts {}", turn_count);
+                ok = false;
+            }
+        }
+        if with_reversi {
+            match reversi_count(depth) {
+                Some(reversi_count) if reversi_count != count => {
+                    print!("  reversi counts {}", reversi_count);
+                    ok = false;
+                }
+                Some(_) => (),
+                None => {
+                    eprintln!("Error: perft was built without the `reversi` feature");
+                    process::exit(1);
+                }
+            }
+        }
+        println!();
+    }
+    if !ok {
+        process::exit(1);
+    }
+}
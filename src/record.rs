@@ -0,0 +1,252 @@
+//! Game records, saved as Othello transcripts: a few header tags followed by
+//! the list of moves, e.g. `f5d6c3d3c4...`, with `pa` for passes.
+//!
+//! ```text
+//! [Dark "Human"]
+//! [Light "Custom AI"]
+//! [Date "2017.05.21"]
+//! [Result "33-31"]
+//! [Opening "Tiger"]
+//! f5d6c3d3c4f4f6f3e6e7...
+//! ```
+
+use game::{Coord, IsPlayer, PlayerAction, Side, Turn};
+use bit_board;
+use bit_board::BitBoard;
+use openings;
+use {Result, Action, OtherAction};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The token used for passes in transcripts.
+const PASS: &str = "pa";
+/// Ends the results of the games lost on time, as in GGF.
+pub const TIME_LOSS: &str = ":t";
+
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub dark: String,
+    pub light: String,
+    pub date: String,
+    /// Final score as `dark-light`, followed by `:t` if the game was lost on time,
+    /// or `*` for unfinished games.
+    pub result: String,
+    /// The name of the opening played, if known.
+    pub opening: Option<String>,
+    /// The moves played, without passes.
+    pub moves: Vec<Coord>,
+    /// The mover's evaluation of each move, in disks, where known.
+    pub evals: Vec<Option<f64>>,
+}
+
+impl GameRecord {
+    /// An empty record of a game played today.
+    pub fn new(dark: &str, light: &str) -> GameRecord {
+        GameRecord {
+            dark: dark.to_string(),
+            light: light.to_string(),
+            date: today(),
+            result: "*".to_string(),
+            opening: None,
+            moves: Vec::new(),
+            evals: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> ::std::result::Result<GameRecord, String> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|err| format!("cannot read {}: {}", path, err))?;
+        text.parse()
+    }
+
+    pub fn save(&self, path: &str) -> ::std::result::Result<(), String> {
+        File::create(path)
+            .and_then(|mut file| file.write_all(self.to_string().as_bytes()))
+            .map_err(|err| format!("cannot write {}: {}", path, err))
+    }
+
+    /// The positions before each move, followed by the final one.
+    /// Fails if a move is illegal.
+    pub fn positions(&self) -> ::std::result::Result<Vec<BitBoard>, String> {
+        let mut positions = vec![bit_board::START];
+        let mut board = bit_board::START;
+        for (n, &coord) in self.moves.iter().enumerate() {
+            board = play(board, coord).ok_or_else(|| format!("move {} ({}) is illegal", n + 1, format_coord(coord)))?;
+            positions.push(board);
+        }
+        Ok(positions)
+    }
+
+    /// Sets the opening from the moves played, if they follow a named opening.
+    pub fn set_opening(&mut self) {
+        self.opening = openings::game_opening(&self.moves).map(str::to_string);
+    }
+
+    /// Sets the result from the disks on the final board.
+    pub fn set_result(&mut self, dark: u16, light: u16) {
+        self.result = format!("{}-{}", dark, light);
+    }
+
+    /// Sets the result of a game lost on time by `loser`: the winner gets all the disks.
+    pub fn set_time_loss(&mut self, loser: Side) {
+        match loser {
+            Side::Dark => self.set_result(0, 64),
+            Side::Light => self.set_result(64, 0),
+        }
+        self.result.push_str(TIME_LOSS);
+    }
+
+    /// The side which lost the game on time, if it was.
+    pub fn time_loser(&self) -> Option<Side> {
+        if !self.result.ends_with(TIME_LOSS) {
+            None
+        } else if self.result.starts_with("0-") {
+            Some(Side::Dark)
+        } else {
+            Some(Side::Light)
+        }
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[Dark \"{}\"]", self.dark)?;
+        writeln!(f, "[Light \"{}\"]", self.light)?;
+        writeln!(f, "[Date \"{}\"]", self.date)?;
+        writeln!(f, "[Result \"{}\"]", self.result)?;
+        if let Some(ref opening) = self.opening {
+            writeln!(f, "[Opening \"{}\"]", opening)?;
+        }
+        // Passes are not stored, so they are found out by replaying the game
+        let mut board = bit_board::START;
+        for &coord in self.moves.iter() {
+            if board.legal_moves() == 0 {
+                write!(f, "{}", PASS)?;
+                board = board.pass();
+            }
+            write!(f, "{}", format_coord(coord))?;
+            board = play(board, coord).unwrap_or(board);
+        }
+        writeln!(f)
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = String;
+
+    fn from_str(text: &str) -> ::std::result::Result<GameRecord, String> {
+        let mut record = GameRecord {
+            dark: String::new(),
+            light: String::new(),
+            date: String::new(),
+            result: "*".to_string(),
+            opening: None,
+            moves: Vec::new(),
+            evals: Vec::new(),
+        };
+        let mut transcript = String::new();
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('[') {
+                let tag = line.trim_matches(|c| c == '[' || c == ']');
+                let mut parts = tag.splitn(2, ' ');
+                let name = parts.next().unwrap_or("");
+                let value = parts.next().unwrap_or("").trim().trim_matches('"').to_string();
+                match name {
+                    "Dark" => record.dark = value,
+                    "Light" => record.light = value,
+                    "Date" => record.date = value,
+                    "Result" => record.result = value,
+                    "Opening" => record.opening = Some(value),
+                    _ => (),
+                }
+            } else {
+                transcript.extend(line.chars().filter(|c| !c.is_whitespace()));
+            }
+        }
+        let transcript = transcript.to_lowercase();
+        let mut chars = transcript.chars();
+        while let Some(first) = chars.next() {
+            let token: String = [first, chars.next().unwrap_or(' ')].iter().collect();
+            if token == PASS || token == "--" {
+                continue;
+            }
+            record.moves.push(parse_coord(&token).ok_or_else(|| format!("invalid move \"{}\"", token.trim()))?);
+        }
+        record.positions()?;
+        Ok(record)
+    }
+}
+
+/// Plays `coord` on `board`, passing first if the side to move has no legal move.
+pub fn play(board: BitBoard, coord: Coord) -> Option<BitBoard> {
+    let board = if board.legal_moves() == 0 { board.pass() } else { board };
+    board.play(coord).ok()
+}
+
+/// Formats a coordinate as in transcripts, e.g. `f5`.
+pub fn format_coord(coord: Coord) -> String {
+    format!("{}{}", (b'a' + coord.get_col() as u8) as char, coord.get_row() + 1)
+}
+
+/// Parses a coordinate such as `f5` (or `F5`).
+pub fn parse_coord(text: &str) -> Option<Coord> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    match (bytes[0].to_ascii_lowercase(), bytes[1]) {
+        (col @ b'a'..=b'h', row @ b'1'..=b'8') => Some(Coord::new((row - b'1') as usize, (col - b'a') as usize)),
+        _ => None,
+    }
+}
+
+/// Today's date, as `yyyy.mm.dd`.
+fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+    // Converts days since 1970-01-01 into a civil date
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{}.{:02}.{:02}", year, month, day)
+}
+
+/// A player that first plays the given moves, then lets another player take over.
+/// Both sides of a resumed game share the same list of moves.
+pub struct ReplayPlayer<'a> {
+    moves: &'a RefCell<VecDeque<Coord>>,
+    player: &'a dyn IsPlayer<OtherAction>,
+}
+
+impl<'a> ReplayPlayer<'a> {
+    pub fn new(moves: &'a RefCell<VecDeque<Coord>>, player: &'a dyn IsPlayer<OtherAction>) -> ReplayPlayer<'a> {
+        ReplayPlayer {
+            moves,
+            player,
+        }
+    }
+}
+
+impl<'a> IsPlayer<OtherAction> for ReplayPlayer<'a> {
+    fn make_move(&self, turn: &Turn) -> Result<Action> {
+        match self.moves.borrow_mut().pop_front() {
+            Some(coord) => Ok(PlayerAction::Move(coord)),
+            None => self.player.make_move(turn),
+        }
+    }
+}
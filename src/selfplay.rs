@@ -0,0 +1,386 @@
+//! Games between AIs, and the statistics to compare them.
+
+use {Result, Action, OtherAction};
+use game::{Coord, IsPlayer, PlayerAction, ReversiError, Side, Turn};
+use ai_player::{AiKind, AiPlayer, Score};
+use bit_board;
+use bit_board::BitBoard;
+use book;
+use book::BookMode;
+use custom_ai;
+use custom_ai::{Deadline, Evaluator, SearchConfig, SearchContext, SearchLimit, SilentObserver};
+use record;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use rayon;
+
+/// Depth of the search rating the balance of openings.
+const OPENING_DEPTH: usize = 4;
+
+/// The AIs of self-play games.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerKind {
+    Weak,
+    Medium,
+    Strong,
+    /// The custom AI, searching to the given depth.
+    Custom(usize),
+    /// The custom AI, thinking the given time per move.
+    Timed(Duration),
+}
+
+/// An AI taking part in self-play games.
+#[derive(Clone)]
+pub struct PlayerSpec {
+    pub kind: PlayerKind,
+    /// The pattern weights of this player, if not the default ones.
+    pub weights: Option<String>,
+    /// The opening book of this player, if not the default one.
+    pub book: Option<String>,
+    /// How the custom AI searches. Self-play games are played in parallel,
+    /// so by default each search keeps to the calling thread, and openings are
+    /// played more than once, so by default the root moves get noise.
+    pub config: SearchConfig,
+}
+
+impl PlayerSpec {
+    pub fn new(kind: PlayerKind) -> PlayerSpec {
+        let mut config = SearchConfig::default();
+        config.set_threads(1);
+        config.noise = true;
+        PlayerSpec { kind, weights: None, book: None, config }
+    }
+
+    /// Loads the weights and the book of this player into its config.
+    pub fn load(&mut self, book_depth: usize, book_mode: BookMode) -> ::std::result::Result<(), String> {
+        if let Some(ref path) = self.weights {
+            self.config.evaluator = Arc::new(Evaluator::load(path)?);
+        }
+        if let Some(ref path) = self.book {
+            self.config.book = Some(Arc::new(book::load_book(path, book_depth, book_mode)?));
+        }
+        Ok(())
+    }
+
+    /// The move of the custom AI, searching within `limit`.
+    fn search(&self, turn: &Turn, limit: SearchLimit) -> Result<Action> {
+        Ok(PlayerAction::Move(custom_ai::find_best_move_custom(turn, limit, &self.config, &SilentObserver)?))
+    }
+}
+
+impl IsPlayer<OtherAction> for PlayerSpec {
+    fn make_move(&self, turn: &Turn) -> Result<Action> {
+        let ai = |kind| AiPlayer::new(kind, self.config.clone());
+        match self.kind {
+            PlayerKind::Weak => ai(AiKind::Weak).make_move(turn),
+            PlayerKind::Medium => ai(AiKind::Medium).make_move(turn),
+            PlayerKind::Strong => ai(AiKind::Strong).make_move(turn),
+            PlayerKind::Custom(depth) => self.search(turn, SearchLimit::Depth(depth)),
+            PlayerKind::Timed(time) => self.search(turn, SearchLimit::Time(time)),
+        }
+    }
+}
+
+impl fmt::Display for PlayerSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            PlayerKind::Weak => write!(f, "weak")?,
+            PlayerKind::Medium => write!(f, "medium")?,
+            PlayerKind::Strong => write!(f, "strong")?,
+            PlayerKind::Custom(depth) => write!(f, "custom:{}", depth)?,
+            PlayerKind::Timed(time) => {
+                write!(f, "timed:{}", time.as_secs() as f64 + time.subsec_nanos() as f64 * 1e-9)?
+            }
+        }
+        if let Some(ref path) = self.weights {
+            write!(f, ",weights={}", path)?;
+        }
+        if let Some(ref path) = self.book {
+            write!(f, ",book={}", path)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PlayerSpec {
+    type Err = String;
+
+    /// Parses `weak`, `medium`, `strong`, `custom[:depth]` or `timed:seconds`,
+    /// followed by `,weights=FILE` and `,book=FILE` if needed.
+    /// The files are not loaded yet, see `load`.
+    fn from_str(text: &str) -> ::std::result::Result<PlayerSpec, String> {
+        let mut options = text.split(',');
+        let text = options.next().unwrap_or("").to_lowercase();
+        let mut parts = text.splitn(2, ':');
+        let kind = match (parts.next().unwrap_or(""), parts.next()) {
+            ("weak", None) => PlayerKind::Weak,
+            ("medium", None) => PlayerKind::Medium,
+            ("strong", None) => PlayerKind::Strong,
+            ("custom", None) => PlayerKind::Custom(custom_ai::USUAL_DEPTH),
+            ("custom", Some(depth)) => {
+                match depth.parse::<usize>() {
+                    Ok(depth) if depth > 0 => PlayerKind::Custom(depth),
+                    _ => return Err(format!("invalid depth \"{}\"", depth)),
+                }
+            }
+            ("timed", Some(secs)) => {
+                match secs.parse::<f64>() {
+                    Ok(secs) if secs > 0.0 => {
+                        PlayerKind::Timed(Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32))
+                    }
+                    _ => return Err(format!("invalid time \"{}\"", secs)),
+                }
+            }
+            _ => return Err(format!("invalid player \"{}\"", text)),
+        };
+        let mut player = PlayerSpec::new(kind);
+        for option in options {
+            let mut parts = option.splitn(2, '=');
+            match (parts.next().unwrap_or("").to_lowercase().as_str(), parts.next()) {
+                ("weights", Some(path)) if !path.is_empty() => player.weights = Some(path.to_string()),
+                ("book", Some(path)) if !path.is_empty() => player.book = Some(path.to_string()),
+                _ => return Err(format!("invalid player option \"{}\"", option)),
+            }
+        }
+        Ok(player)
+    }
+}
+
+/// Plays a game from the position after `opening`, returning the final disks of Dark and Light.
+pub fn play_game(dark: &dyn IsPlayer<OtherAction>, light: &dyn IsPlayer<OtherAction>, opening: &[Coord])
+                 -> Result<(u16, u16)> {
+    play_record(dark, light, opening).map(|(_, score)| score)
+}
+
+/// Like `play_game`, but also returns all the moves of the game, the opening included.
+pub fn play_record(dark: &dyn IsPlayer<OtherAction>, light: &dyn IsPlayer<OtherAction>, opening: &[Coord])
+                   -> Result<(Vec<Coord>, (u16, u16))> {
+    let mut turn = Turn::first_turn();
+    let mut moves = opening.to_vec();
+    for &coord in opening.iter() {
+        turn.make_move(coord)?;
+    }
+    while let Some(side) = turn.get_state() {
+        let player = match side {
+            Side::Dark => dark,
+            Side::Light => light,
+        };
+        match player.make_move(&turn)? {
+            PlayerAction::Move(coord) => {
+                turn.make_move(coord)?;
+                moves.push(coord);
+            }
+            _ => return Err(ReversiError::EndedGame(turn)),
+        }
+    }
+    Ok((moves, turn.get_score()))
+}
+
+/// The `count` most balanced openings of `plies` moves, according to a shallow search.
+/// Openings are all taken starting with f5, as the other first moves are symmetric to it,
+/// and transpositions are left out.
+pub fn balanced_openings(plies: usize, count: usize) -> Vec<Vec<Coord>> {
+    let mut lines = Vec::new();
+    let mut seen = HashSet::new();
+    let first = Coord::new(4, 5);
+    let board = record::play(bit_board::START, first).expect("f5 is a legal first move");
+    expand_openings(board, &mut vec![first], plies, &mut seen, &mut lines);
+    let config = SearchConfig::default();
+    let tt = config.new_table();
+    let never = Deadline::never();
+    let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &SilentObserver };
+    let mut rated: Vec<(f64, Vec<Coord>)> = lines.into_iter()
+        .map(|(board, line)| {
+            let (my, opp) = (board.mover(), board.opponent());
+            let mut moves_and_scores = Vec::new();
+            let mut nnodes = 0;
+            custom_ai::ai_eval_with_depth(my, opp, OPENING_DEPTH, bit_board::valid_moves_set(my, opp),
+                                          &mut moves_and_scores, &ctx, &mut nnodes);
+            let unbalance = match moves_and_scores.first() {
+                Some(&(_, Score::Running(val))) => val.abs(),
+                Some(&(_, Score::Ended(diff))) => (diff as f64).abs() * 1e3,
+                None => f64::INFINITY,
+            };
+            (unbalance, line)
+        })
+        .collect();
+    rated.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("Unbalances are not NaN"));
+    rated.into_iter().take(count).map(|(_, line)| line).collect()
+}
+
+fn expand_openings(board: BitBoard,
+                   line: &mut Vec<Coord>,
+                   plies: usize,
+                   seen: &mut HashSet<BitBoard>,
+                   lines: &mut Vec<(BitBoard, Vec<Coord>)>) {
+    if board.legal_moves() == 0 || !seen.insert(board) {
+        return;
+    }
+    if line.len() >= plies {
+        lines.push((board, line.clone()));
+        return;
+    }
+    for coord in board.moves() {
+        if let Some(next) = record::play(board, coord) {
+            line.push(coord);
+            expand_openings(next, line, plies, seen, lines);
+            line.pop();
+        }
+    }
+}
+
+/// Wins, draws and losses of a player against another.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// The sum of the disk differences of the games.
+    pub disk_diff: i64,
+}
+
+impl Stats {
+    /// Adds a game, given the final disks of the player and of its opponent.
+    pub fn add(&mut self, mine: u16, theirs: u16) {
+        if mine > theirs {
+            self.wins += 1;
+        } else if mine < theirs {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+        self.disk_diff += mine as i64 - theirs as i64;
+    }
+
+    /// Adds the games of `other`.
+    pub fn merge(&mut self, other: &Stats) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+        self.disk_diff += other.disk_diff;
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The points scored, a draw counting as half a win.
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    /// The Elo difference implied by the score, with the half-width of its 95% confidence interval.
+    pub fn elo(&self) -> (f64, f64) {
+        let n = self.games() as f64;
+        if n == 0.0 {
+            return (0.0, f64::INFINITY);
+        }
+        let score = self.points() / n;
+        let variance = (self.wins as f64 * (1.0 - score).powi(2) + self.draws as f64 * (0.5 - score).powi(2) +
+                        self.losses as f64 * score.powi(2)) / n;
+        let margin = 1.96 * (variance / n).sqrt();
+        (elo_from_score(score), (elo_from_score(score + margin) - elo_from_score(score - margin)) / 2.0)
+    }
+}
+
+/// The Elo difference of a player expected to score `score` (between 0 and 1) per game.
+pub fn elo_from_score(score: f64) -> f64 {
+    let score = score.clamp(1e-3, 1.0 - 1e-3);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// The score per game expected from a player with an Elo difference of `elo`.
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// A sequential probability ratio test of the hypotheses that a candidate is
+/// `elo0` (H0) or `elo1` (H1) Elo stronger than a baseline, with error rates
+/// `alpha` (accepting H1 when H0 holds) and `beta` (accepting H0 when H1 holds).
+#[derive(Debug, Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Sprt {
+        Sprt {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// The bounds of the log-likelihood ratio, below which H0 and above which H1 is accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /// The log-likelihood ratio of the games of the candidate, in the normal approximation.
+    /// It stays 0 until the results vary at all.
+    pub fn llr(&self, stats: &Stats) -> f64 {
+        let n = stats.games() as f64;
+        if n == 0.0 {
+            return 0.0;
+        }
+        let score = stats.points() / n;
+        let variance = (stats.wins as f64 * (1.0 - score).powi(2) + stats.draws as f64 * (0.5 - score).powi(2) +
+                        stats.losses as f64 * score.powi(2)) / n;
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let (score0, score1) = (score_from_elo(self.elo0), score_from_elo(self.elo1));
+        n * (score1 - score0) * (2.0 * score - score0 - score1) / (2.0 * variance)
+    }
+
+    /// `Some(true)` if H1 is accepted, `Some(false)` if H0 is, `None` if more games are needed.
+    pub fn decision(&self, stats: &Stats) -> Option<bool> {
+        let llr = self.llr(stats);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(true)
+        } else if llr <= lower {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Plays pairs of games between `candidate` and `baseline` (one with each colour,
+/// from the same opening) until `test` reaches a decision or `max_games` are played,
+/// calling `report` after each pair. Returns the decision, if any, and the candidate's games.
+pub fn run_sprt<F>(candidate: &(dyn IsPlayer<OtherAction> + Sync),
+                   baseline: &(dyn IsPlayer<OtherAction> + Sync),
+                   openings: &[Vec<Coord>],
+                   test: &Sprt,
+                   max_games: u32,
+                   mut report: F)
+                   -> Result<(Option<bool>, Stats)>
+    where F: FnMut(&Stats)
+{
+    let mut stats = Stats::default();
+    let no_opening = Vec::new();
+    let mut openings = openings.iter().cycle();
+    while stats.games() < max_games {
+        let opening = openings.next().unwrap_or(&no_opening);
+        let (first, second) = rayon::join(|| play_game(candidate, baseline, opening),
+                                          || play_game(baseline, candidate, opening));
+        let (dark, light) = first?;
+        stats.add(dark, light);
+        let (dark, light) = second?;
+        stats.add(light, dark);
+        report(&stats);
+        if let Some(accepted) = test.decision(&stats) {
+            return Ok((Some(accepted), stats));
+        }
+    }
+    Ok((None, stats))
+}
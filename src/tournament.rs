@@ -0,0 +1,266 @@
+//! Plays self-play tournaments and SPRT tests between AI players.
+
+#![crate_name = "tournament"]
+#![crate_type = "bin"]
+#![cfg_attr(feature="clippy", feature(plugin))]
+#![cfg_attr(feature="clippy", plugin(clippy))]
+
+extern crate rusthello_lib;
+extern crate rayon;
+
+use rayon::prelude::*;
+use rusthello_lib::game::Coord;
+use rusthello_lib::record::GameRecord;
+use rusthello_lib::{book, selfplay};
+use rusthello_lib::book::BookMode;
+use rusthello_lib::custom_ai::Evaluator;
+use rusthello_lib::selfplay::{PlayerSpec, Sprt, Stats};
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: tournament [options] PLAYER PLAYER...
+
+Plays every opening twice, once with each colour, for each pairing of players.
+Players are weak, medium, strong, custom[:depth] or timed:seconds, each optionally
+followed by ,weights=FILE and ,book=FILE to use its own pattern weights or opening book.
+
+Options:
+    --gauntlet       only pair the first player with each of the others
+    --openings N     number of balanced openings to play (default 10)
+    --plies N        length of the generated openings (default 6)
+    --file FILE      read the openings from FILE, one transcript per line
+    --weights FILE   default pattern weights of the players
+    --book FILE      default opening book of the players
+    --book-depth N   leave the book after N moves (default 60)
+    --book-mode MODE deterministic, weighted or best (default weighted)
+
+SPRT mode, testing whether the first player is stronger than the second:
+    --sprt ELO0 ELO1 play until H0 (ELO0 stronger) or H1 (ELO1 stronger) is accepted
+    --alpha A        probability of accepting H1 when H0 holds (default 0.05)
+    --beta B         probability of accepting H0 when H1 holds (default 0.05)
+    --max-games N    stop after N games anyway (default 1000)";
+
+struct Options {
+    gauntlet: bool,
+    openings: usize,
+    plies: usize,
+    file: Option<String>,
+    sprt: Option<Sprt>,
+    max_games: u32,
+    players: Vec<PlayerSpec>,
+    book: Option<String>,
+    book_depth: usize,
+    book_mode: BookMode,
+}
+
+fn parse_number<T: ::std::str::FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|value| value.parse::<T>().ok())
+        .ok_or_else(|| format!("{} needs a number", arg))
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        gauntlet: false,
+        openings: 10,
+        plies: 6,
+        file: None,
+        sprt: None,
+        max_games: 1000,
+        players: Vec::new(),
+        book: None,
+        book_depth: 60,
+        book_mode: BookMode::WeightedRandom,
+    };
+    let mut alpha = None;
+    let mut beta = None;
+    let mut evaluator = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--gauntlet" => options.gauntlet = true,
+            "--openings" => options.openings = parse_number(&arg, args.next())?,
+            "--plies" => options.plies = parse_number(&arg, args.next())?,
+            "--sprt" => {
+                let elo0 = parse_number(&arg, args.next())?;
+                let elo1 = parse_number(&arg, args.next())?;
+                options.sprt = Some(Sprt::new(elo0, elo1));
+            }
+            "--alpha" => alpha = Some(parse_number(&arg, args.next())?),
+            "--beta" => beta = Some(parse_number(&arg, args.next())?),
+            "--max-games" => options.max_games = parse_number(&arg, args.next())?,
+            "--weights" => {
+                let path = args.next().ok_or_else(|| "--weights needs a file name".to_string())?;
+                evaluator = Some(Arc::new(Evaluator::load(&path)?));
+            }
+            "--book" => options.book = Some(args.next().ok_or_else(|| "--book needs a file name".to_string())?),
+            "--book-depth" => options.book_depth = parse_number(&arg, args.next())?,
+            "--book-mode" => {
+                options.book_mode = args.next().ok_or_else(|| "--book-mode needs a mode".to_string())?.parse()?
+            }
+            "--file" => options.file = Some(args.next().ok_or_else(|| "--file needs a file name".to_string())?),
+            "-h" | "--help" => return Err(String::new()),
+            _ => options.players.push(arg.parse()?),
+        }
+    }
+    if options.players.len() < 2 {
+        return Err("at least two players are needed".to_string());
+    }
+    if let Some(ref mut sprt) = options.sprt {
+        if options.players.len() != 2 {
+            return Err("SPRT mode needs exactly two players".to_string());
+        }
+        sprt.alpha = alpha.unwrap_or(sprt.alpha);
+        sprt.beta = beta.unwrap_or(sprt.beta);
+    }
+    let book = match options.book {
+        Some(ref path) => Some(Arc::new(book::load_book(path, options.book_depth, options.book_mode)?)),
+        None => None,
+    };
+    for player in options.players.iter_mut() {
+        if let Some(ref evaluator) = evaluator {
+            player.config.evaluator = evaluator.clone();
+        }
+        if book.is_some() {
+            player.config.book = book.clone();
+        }
+        player.load(options.book_depth, options.book_mode)?;
+    }
+    Ok(options)
+}
+
+fn read_openings(path: &str) -> Result<Vec<Vec<Coord>>, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|err| format!("cannot read {}: {}", path, err))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.parse::<GameRecord>().map(|record| record.moves))
+        .collect()
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("Error: {}\n", err);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    let openings = match options.file {
+        Some(ref path) => read_openings(path).unwrap_or_else(|err| {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }),
+        None => selfplay::balanced_openings(options.plies, options.openings),
+    };
+    let players = &options.players;
+
+    if let Some(ref sprt) = options.sprt {
+        let (lower, upper) = sprt.bounds();
+        println!("SPRT of {} against {}: elo0 = {}, elo1 = {}, alpha = {}, beta = {}",
+                 players[0], players[1], sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta);
+        let (decision, stats) = selfplay::run_sprt(&players[0], &players[1], &openings, sprt, options.max_games, |stats| {
+                println!("Games {:>5}: +{} ={} -{}, LLR {:+.3} ({:+.3}, {:+.3})",
+                         stats.games(), stats.wins, stats.draws, stats.losses,
+                         sprt.llr(stats), lower, upper);
+            })
+            .expect("Self-play games should end without errors");
+        let (elo, margin) = stats.elo();
+        println!("\n{} after {} games, Elo {:+.1} ± {:.1}",
+                 match decision {
+                     Some(true) => "H1 accepted",
+                     Some(false) => "H0 accepted",
+                     None => "No decision",
+                 },
+                 stats.games(),
+                 elo,
+                 margin);
+        return;
+    }
+
+    // Every game, as (first player, second player, opening, whether the first one is Dark)
+    let mut games = Vec::new();
+    for first in 0 .. players.len() {
+        for second in first + 1 .. players.len() {
+            if options.gauntlet && first > 0 {
+                continue;
+            }
+            for opening in openings.iter() {
+                games.push((first, second, opening, true));
+                games.push((first, second, opening, false));
+            }
+        }
+    }
+    println!("{} players, {} openings, {} games", players.len(), openings.len(), games.len());
+
+    let results: Vec<(usize, usize, u16, u16)> = games.par_iter()
+        .map(|&(first, second, opening, first_dark)| {
+            let (dark, light) = if first_dark { (first, second) } else { (second, first) };
+            let (dark_disks, light_disks) = selfplay::play_game(&players[dark], &players[light], opening)
+                .expect("Self-play games should end without errors");
+            println!("{} vs {}: {}-{}", players[dark], players[light], dark_disks, light_disks);
+            (dark, light, dark_disks, light_disks)
+        })
+        .collect();
+
+    // crosstable[i][j] holds the results of player i against player j
+    let mut crosstable = vec![vec![Stats::default(); players.len()]; players.len()];
+    for &(dark, light, dark_disks, light_disks) in results.iter() {
+        crosstable[dark][light].add(dark_disks, light_disks);
+        crosstable[light][dark].add(light_disks, dark_disks);
+    }
+    let mut totals: Vec<(usize, Stats)> = crosstable.iter()
+        .enumerate()
+        .map(|(player, row)| {
+            let mut total = Stats::default();
+            for stats in row.iter() {
+                total.merge(stats);
+            }
+            (player, total)
+        })
+        .collect();
+    totals.sort_by(|a, b| b.1.points().partial_cmp(&a.1.points()).expect("Points are not NaN"));
+
+    println!("\n    {:<14} {:>5} {:>5} {:>5} {:>5} {:>7} {:>7} {:>16}",
+             "Player", "Games", "Wins", "Draws", "Loss", "Points", "Disks", "Elo vs field");
+    for (rank, &(player, ref total)) in totals.iter().enumerate() {
+        let (elo, margin) = total.elo();
+        println!("{:>2}. {:<14} {:>5} {:>5} {:>5} {:>5} {:>7.1} {:>+7.2} {:>+7.1} ± {:<6.1}",
+                 rank + 1,
+                 players[player].to_string(),
+                 total.games(),
+                 total.wins,
+                 total.draws,
+                 total.losses,
+                 total.points(),
+                 total.disk_diff as f64 / total.games() as f64,
+                 elo,
+                 margin);
+    }
+
+    println!("\nCrosstable (points scored by the row player against the column player):");
+    print!("    {:<14}", "");
+    for n in 0 .. players.len() {
+        print!(" {:>9}", n + 1);
+    }
+    println!();
+    for (rank, &(player, _)) in totals.iter().enumerate() {
+        print!("{:>2}. {:<14}", rank + 1, players[player].to_string());
+        for &(opponent, _) in totals.iter() {
+            let stats = &crosstable[player][opponent];
+            if stats.games() == 0 {
+                print!(" {:>9}", "-");
+            } else {
+                print!(" {:>9}", format!("{}/{}", stats.points(), stats.games()));
+            }
+        }
+        println!();
+    }
+}
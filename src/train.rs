@@ -0,0 +1,202 @@
+//! Fits the pattern weights of the custom AI to saved and self-played games.
+
+#![crate_name = "train"]
+#![crate_type = "bin"]
+#![cfg_attr(feature="clippy", feature(plugin))]
+#![cfg_attr(feature="clippy", plugin(clippy))]
+
+extern crate rusthello_lib;
+extern crate rand;
+extern crate rayon;
+
+use rand::Rng;
+use rayon::prelude::*;
+use rusthello_lib::{bit_board, custom_ai, selfplay};
+use rusthello_lib::bit_board::BitBoard;
+use rusthello_lib::custom_ai::{Deadline, Evaluator, SearchConfig, SearchContext, SilentObserver, SolveMode,
+                               SolveScore};
+use rusthello_lib::game::Side;
+use rusthello_lib::ggf::GgfGame;
+use rusthello_lib::pattern_eval::{PatternWeights, Sample};
+use rusthello_lib::record::GameRecord;
+use rusthello_lib::selfplay::{PlayerKind, PlayerSpec};
+use std::env;
+use std::process;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: train [options] [FILE...]
+
+Fits the pattern weights of the custom AI to positions with known final disk differences,
+taken from the saved games in FILE... (transcripts, or GGF if named *.ggf) and from self-play.
+
+Options:
+    --selfplay N     also play N games of the custom AI against itself (default 0)
+    --depth D        search depth of the self-play games (default 2)
+    --solve E        label the positions with E empty cells or less by solving them (default 0)
+    --epochs N       passes of gradient descent over the positions (default 20)
+    --rate R         learning rate (default 0.002)
+    --init FILE      start from the weights in FILE (also used by self-play) instead of zeros
+    --output FILE    where to write the weights (default weights.bin)";
+
+struct Options {
+    files: Vec<String>,
+    selfplay: usize,
+    depth: usize,
+    solve: usize,
+    epochs: usize,
+    rate: f32,
+    init: Option<String>,
+    output: String,
+}
+
+fn parse_number<T: ::std::str::FromStr>(arg: &str, value: Option<String>) -> Result<T, String> {
+    value.and_then(|value| value.parse::<T>().ok())
+        .ok_or_else(|| format!("{} needs a number", arg))
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        files: Vec::new(),
+        selfplay: 0,
+        depth: 2,
+        solve: 0,
+        epochs: 20,
+        rate: 0.002,
+        init: None,
+        output: "weights.bin".to_string(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--selfplay" => options.selfplay = parse_number(&arg, args.next())?,
+            "--depth" => options.depth = parse_number(&arg, args.next())?,
+            "--solve" => options.solve = parse_number(&arg, args.next())?,
+            "--epochs" => options.epochs = parse_number(&arg, args.next())?,
+            "--rate" => options.rate = parse_number(&arg, args.next())?,
+            "--init" => options.init = Some(args.next().ok_or_else(|| "--init needs a file name".to_string())?),
+            "--output" => options.output = args.next().ok_or_else(|| "--output needs a file name".to_string())?,
+            "-h" | "--help" => return Err(String::new()),
+            _ => options.files.push(arg),
+        }
+    }
+    if options.files.is_empty() && options.selfplay == 0 {
+        return Err("there are no games to train on".to_string());
+    }
+    Ok(options)
+}
+
+/// Adds the positions of a game to `samples`, if the game is over.
+fn add_game(positions: &[BitBoard], samples: &mut Vec<Sample>) {
+    let last = match positions.last() {
+        Some(&board) => board,
+        None => return,
+    };
+    if !last.is_game_over() {
+        return;
+    }
+    let black_diff = last.disc_count(Side::Dark) as f32 - last.disc_count(Side::Light) as f32;
+    for board in positions[.. positions.len() - 1].iter() {
+        samples.push(Sample {
+            my: board.mover(),
+            opp: board.opponent(),
+            diff: if board.black_to_move() { black_diff } else { -black_diff },
+        });
+    }
+}
+
+fn load_games(path: &str, samples: &mut Vec<Sample>) -> Result<(), String> {
+    if path.to_lowercase().ends_with(".ggf") {
+        for game in GgfGame::load_all(path)? {
+            add_game(&game.positions()?, samples);
+        }
+    } else {
+        add_game(&GameRecord::load(path)?.positions()?, samples);
+    }
+    Ok(())
+}
+
+/// The exact final disk difference of a position, for the side to move.
+fn solve(my: u64, opp: u64, ctx: &SearchContext) -> i16 {
+    // The side to move does not matter to the solver
+    let board = BitBoard::from_my_opp(my, opp, true);
+    match custom_ai::solve(board, SolveMode::Exact, ctx).map(|solution| solution.score) {
+        Some(SolveScore::Exact(diff)) => diff,
+        _ => unreachable!("Exact solves without a deadline give exact scores"),
+    }
+}
+
+fn fail(err: String) -> ! {
+    eprintln!("Error: {}", err);
+    process::exit(1);
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("Error: {}\n", err);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    let mut weights = match options.init {
+        Some(ref path) => PatternWeights::load(path).unwrap_or_else(|err| fail(err)),
+        None => PatternWeights::zeros(),
+    };
+
+    let mut samples = Vec::new();
+    for path in options.files.iter() {
+        load_games(path, &mut samples).unwrap_or_else(|err| fail(err));
+    }
+    if options.selfplay > 0 {
+        println!("Playing {} games...", options.selfplay);
+        let mut player = PlayerSpec::new(PlayerKind::Custom(options.depth));
+        // The games are played with the weights to start from, if any
+        if options.init.is_some() {
+            player.config.evaluator = Arc::new(Evaluator::Pattern(weights.clone()));
+        }
+        let openings = selfplay::balanced_openings(4, options.selfplay);
+        let games: Vec<_> = (0 .. options.selfplay)
+            .into_par_iter()
+            .map(|n| {
+                let opening = openings.get(n % openings.len().max(1)).cloned().unwrap_or_default();
+                let (moves, _) = selfplay::play_record(&player, &player, &opening)
+                    .expect("Self-play games should end without errors");
+                let mut record = GameRecord::new("", "");
+                record.moves = moves;
+                record.positions().expect("Self-play moves are legal")
+            })
+            .collect();
+        for positions in games.iter() {
+            add_game(positions, &mut samples);
+        }
+    }
+    if options.solve > 0 {
+        println!("Solving endgames...");
+        let config = SearchConfig::default();
+        let tt = config.new_table();
+        let never = Deadline::never();
+        let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &SilentObserver };
+        for sample in samples.iter_mut() {
+            if 64 - bit_board::get_tempo(sample.my, sample.opp) as usize <= options.solve {
+                sample.diff = solve(sample.my, sample.opp, &ctx) as f32;
+            }
+        }
+    }
+    if samples.is_empty() {
+        fail("there are no finished games to train on".to_string());
+    }
+    println!("Training on {} positions", samples.len());
+
+    let mut rng = rand::thread_rng();
+    for epoch in 0 .. options.epochs {
+        rng.shuffle(&mut samples);
+        let rmse = weights.descend(&samples, options.rate);
+        println!("Epoch {:>3}: RMSE {:.3}", epoch + 1, rmse);
+    }
+
+    weights.save(&options.output).unwrap_or_else(|err| fail(err));
+    println!("Weights written to {}", options.output);
+}
@@ -0,0 +1,154 @@
+//! A transposition table for the bitboard searches of `custom_ai`.
+//! Positions are keyed directly by the `(my, opp)` pair of the side to move.
+//!
+//! The table is shared by all the threads of a search without any lock: each slot is
+//! a few atomic words, and the key words are stored xored with the data words, so that
+//! a slot torn by two concurrent writes no longer matches any position
+//! (the "lockless hashing" of Hyatt and Mann).
+
+use ai_player::Score;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Depth recorded for entries computed by the endgame solver, i.e. searched till the end of the game.
+pub const SOLVED: u8 = 64;
+
+/// How the stored score relates to the real value of the position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The real value is at least the stored score.
+    Lower,
+    /// The real value is at most the stored score.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub depth: u8,
+    pub bound: Bound,
+    pub score: Score,
+    /// The best move found in this position, as a singleton set (0 if unknown or a pass).
+    pub best_move: u64,
+}
+
+/// A slot of the table: the key words are xored with the data words.
+struct Slot {
+    my: AtomicU64,
+    opp: AtomicU64,
+    score: AtomicU64,
+    meta: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    mask: usize,
+    /// The search the entries are stored by, wrapping around.
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// Creates a table taking (at most) `size_mb` megabytes.
+    /// The number of entries is rounded down to a power of two.
+    pub fn new(size_mb: usize) -> TranspositionTable {
+        let wanted = ::std::cmp::max(size_mb * 1024 * 1024 / ::std::mem::size_of::<Slot>(), 1);
+        let len = 1usize << (63 - (wanted as u64).leading_zeros());
+        TranspositionTable {
+            slots: (0..len)
+                .map(|_| {
+                    Slot {
+                        my: AtomicU64::new(0),
+                        opp: AtomicU64::new(0),
+                        score: AtomicU64::new(0),
+                        meta: AtomicU64::new(0),
+                    }
+                })
+                .collect(),
+            mask: len - 1,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// Starts a new search: the entries of the former ones are kept, but they make way
+    /// for the new entries however deep they are.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.my.store(0, Ordering::Relaxed);
+            slot.opp.store(0, Ordering::Relaxed);
+            slot.score.store(0, Ordering::Relaxed);
+            slot.meta.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn probe(&self, my: u64, opp: u64) -> Option<Entry> {
+        if (my | opp) == 0 {
+            return None;
+        }
+        let slot = &self.slots[self.index(my, opp)];
+        let (score, meta) = (slot.score.load(Ordering::Relaxed), slot.meta.load(Ordering::Relaxed));
+        let (key_my, key_opp) = (slot.my.load(Ordering::Relaxed), slot.opp.load(Ordering::Relaxed));
+        if key_my == my ^ score ^ meta && key_opp == opp ^ score.rotate_left(32) ^ meta.rotate_left(16) {
+            Some(unpack(score, meta))
+        } else {
+            None
+        }
+    }
+
+    /// Stores a search result. A slot holding another position of the current search
+    /// is overwritten only by a search at least as deep as the one it holds.
+    pub fn store(&self, my: u64, opp: u64, depth: u8, bound: Bound, score: Score, best_move: u64) {
+        let slot = &self.slots[self.index(my, opp)];
+        let generation = self.generation.load(Ordering::Relaxed);
+        let held = slot.meta.load(Ordering::Relaxed);
+        if depth < held as u8 && (held >> 24) as u8 == generation && self.probe(my, opp).is_none() {
+            return;
+        }
+        let (score, meta) = pack(depth, bound, score, best_move, generation);
+        slot.score.store(score, Ordering::Relaxed);
+        slot.meta.store(meta, Ordering::Relaxed);
+        slot.my.store(my ^ score ^ meta, Ordering::Relaxed);
+        slot.opp.store(opp ^ score.rotate_left(32) ^ meta.rotate_left(16), Ordering::Relaxed);
+    }
+
+    fn index(&self, my: u64, opp: u64) -> usize {
+        let hash = (my ^ opp.rotate_left(29)).wrapping_mul(0x9e3779b97f4a7c15) ^ opp.wrapping_mul(0xc2b2ae3d27d4eb4f);
+        (hash ^ (hash >> 32)) as usize & self.mask
+    }
+}
+
+/// Packs an entry into a score word and a meta word (depth, bound, kind of score,
+/// square of the best move, 64 for none, and generation of the search).
+fn pack(depth: u8, bound: Bound, score: Score, best_move: u64, generation: u8) -> (u64, u64) {
+    let (score, ended) = match score {
+        Score::Running(value) => (value.to_bits(), 0),
+        Score::Ended(value) => (value as u16 as u64, 1),
+    };
+    let bound = match bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    let square = if best_move == 0 { 64 } else { best_move.trailing_zeros() as u64 };
+    (score, depth as u64 | bound << 8 | ended << 10 | square << 16 | (generation as u64) << 24)
+}
+
+fn unpack(score: u64, meta: u64) -> Entry {
+    let square = (meta >> 16) & 0x7f;
+    Entry {
+        depth: meta as u8,
+        bound: match (meta >> 8) & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        },
+        score: if (meta >> 10) & 1 == 1 {
+            Score::Ended(score as u16 as i16)
+        } else {
+            Score::Running(f64::from_bits(score))
+        },
+        best_move: if square == 64 { 0 } else { 1 << square },
+    }
+}
@@ -0,0 +1,184 @@
+//! Test module for bitboards and their symmetries.
+
+extern crate rand;
+extern crate rusthello_lib;
+
+use rand::Rng;
+use rusthello_lib::bit_board::{self, BitBoard, Symmetry, SYMMETRIES};
+use rusthello_lib::game::{Coord, ReversiError, Side, Turn};
+
+/// The positions of a few random games.
+fn random_positions() -> Vec<(u64, u64)> {
+    let mut rng = rand::thread_rng();
+    let mut positions = Vec::new();
+    for _ in 0 .. 20 {
+        let (mut my, mut opp) = (bit_board::START.mover(), bit_board::START.opponent());
+        loop {
+            let moves = bit_board::valid_moves_set(my, opp);
+            if moves == 0 {
+                if bit_board::valid_moves_set(opp, my) == 0 {
+                    break;
+                }
+                std::mem::swap(&mut my, &mut opp);
+                continue;
+            }
+            positions.push((my, opp));
+            let squares: Vec<u32> = (0 .. 64).filter(|&square| moves & 1u64 << square != 0).collect();
+            let disk = 1u64 << *rng.choose(&squares).unwrap();
+            let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+            my = nopp;
+            opp = nmy;
+        }
+    }
+    positions
+}
+
+#[test]
+fn test_symmetries_of_coords() {
+    // A corner and a square next to it
+    let a1 = Coord::new(0, 0);
+    let b1 = Coord::new(0, 1);
+    assert_eq!(Symmetry::Rotate90.apply_coord(a1), Coord::new(0, 7));
+    assert_eq!(Symmetry::Rotate90.apply_coord(b1), Coord::new(1, 7));
+    assert_eq!(Symmetry::FlipVertical.apply_coord(b1), Coord::new(7, 1));
+    assert_eq!(Symmetry::FlipHorizontal.apply_coord(b1), Coord::new(0, 6));
+    assert_eq!(Symmetry::FlipDiagonal.apply_coord(b1), Coord::new(1, 0));
+    assert_eq!(Symmetry::FlipAntiDiagonal.apply_coord(b1), Coord::new(6, 7));
+
+    for &symmetry in SYMMETRIES.iter() {
+        for square in 0 .. 64 {
+            let coord = Coord::new(square / 8, square % 8);
+            let disk = 1u64 << square;
+            let moved = symmetry.apply_coord(coord);
+            assert_eq!(symmetry.apply(disk), 1u64 << (moved.get_row() * 8 + moved.get_col()));
+            assert_eq!(symmetry.inverse().apply_coord(moved), coord);
+        }
+    }
+    // The symmetries are all different
+    let images: Vec<u64> = SYMMETRIES.iter().map(|symmetry| symmetry.apply(0x0000_0000_0001_0002)).collect();
+    for (n, image) in images.iter().enumerate() {
+        assert!(!images[.. n].contains(image));
+    }
+}
+
+#[test]
+fn test_symmetries_commute_with_moves() {
+    for (my, opp) in random_positions() {
+        let moves = bit_board::valid_moves_set(my, opp);
+        for &symmetry in SYMMETRIES.iter() {
+            let (smy, sopp) = (symmetry.apply(my), symmetry.apply(opp));
+            assert_eq!(symmetry.inverse().apply(smy), my);
+            assert_eq!(bit_board::valid_moves_set(smy, sopp), symmetry.apply(moves));
+            for square in 0 .. 64 {
+                let disk = 1u64 << square;
+                if moves & disk != 0 {
+                    let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+                    assert_eq!(bit_board::move_bit_board(smy, sopp, symmetry.apply(disk)),
+                               (symmetry.apply(nmy), symmetry.apply(nopp)));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_canonical() {
+    for (my, opp) in random_positions() {
+        let (cmy, copp, symmetry) = bit_board::canonical(my, opp);
+        assert_eq!((symmetry.apply(my), symmetry.apply(opp)), (cmy, copp));
+        for &other in SYMMETRIES.iter() {
+            let (omy, oopp, _) = bit_board::canonical(other.apply(my), other.apply(opp));
+            assert_eq!((omy, oopp), (cmy, copp));
+        }
+    }
+}
+
+#[test]
+fn test_flips_match_reference() {
+    for (my, opp) in random_positions() {
+        let empty = !(my | opp);
+        for square in 0 .. 64 {
+            let disk = 1u64 << square;
+            if empty & disk != 0 {
+                assert_eq!(bit_board::flips_kogge_stone(my, opp, disk),
+                           bit_board::flips_reference(my, opp, disk));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_play_and_pass() {
+    let board = bit_board::START;
+    assert_eq!(board.side_to_move(), Side::Dark);
+    assert_eq!(board.disc_count(Side::Dark), 2);
+    assert_eq!(board.moves().collect::<Vec<Coord>>(),
+               vec![Coord::new(2, 3), Coord::new(3, 2), Coord::new(4, 5), Coord::new(5, 4)]);
+    match board.play(Coord::new(0, 0)) {
+        Err(ReversiError::IllegalMove(coord)) => assert_eq!(coord, Coord::new(0, 0)),
+        other => panic!("a1 was played: {:?}", other),
+    }
+    match board.play(Coord::new(8, 0)) {
+        Err(ReversiError::OutOfBoundCoord(_)) => (),
+        other => panic!("a9 was played: {:?}", other),
+    }
+
+    let next = board.play(Coord::new(4, 5)).unwrap();
+    assert_eq!(next.side_to_move(), Side::Light);
+    assert_eq!((next.disc_count(Side::Dark), next.disc_count(Side::Light)), (4, 1));
+    assert_eq!(next.cell(Coord::new(4, 4)).unwrap(), Some(Side::Dark));
+    assert_eq!(next.pass().pass(), next);
+    assert_eq!(Turn::from(next).get_bit_board(), next);
+
+    // The fastest game: White has no disk left after f5 d6 c5 f4 e7 f6 g5 e6 e3
+    let mut board = bit_board::START;
+    for &(row, col) in [(4, 5), (5, 3), (4, 2), (3, 5), (6, 4), (5, 5), (4, 6), (5, 4), (2, 4)].iter() {
+        assert!(!board.is_game_over());
+        board = board.play(Coord::new(row, col)).unwrap();
+    }
+    assert!(board.is_game_over());
+    assert_eq!(board.disc_count(Side::Light), 0);
+    assert_eq!(board.moves().len(), 0);
+}
+
+#[test]
+fn test_display_and_parse() {
+    let text = "---------------------------OX------XO--------------------------- X";
+    assert_eq!(bit_board::START.to_string(), text);
+    assert_eq!(text.parse::<BitBoard>(), Ok(bit_board::START));
+    assert_eq!("........ ........ ........ ...o*... ...*o... ........ ........ ........ *".parse::<BitBoard>(),
+               Ok(bit_board::START));
+    assert!("---------------------------OX------XO--------------------------- ?".parse::<BitBoard>().is_err());
+    assert!("---------------------------OX------XO-------------------------- X".parse::<BitBoard>().is_err());
+
+    for (my, opp) in random_positions() {
+        let board = BitBoard::from_my_opp(my, opp, my.count_ones() % 2 == 0);
+        assert_eq!(board.to_string().parse::<BitBoard>(), Ok(board));
+    }
+}
+
+#[test]
+fn test_stable_disks() {
+    assert_eq!(bit_board::stable_disks(bit_board::START.mover(), bit_board::START.opponent()), 0);
+    // a1 b1 c1 hold on to the corner, b2 does not
+    assert_eq!(bit_board::stable_disks(0x207, 0x8), 0x7);
+
+    // Stable disks stay until the end of random games
+    let mut rng = rand::thread_rng();
+    for _ in 0 .. 20 {
+        let mut board = bit_board::START;
+        let mut stable = (0, 0);
+        while !board.is_game_over() {
+            stable.0 |= bit_board::stable_disks(board.black(), board.white());
+            stable.1 |= bit_board::stable_disks(board.white(), board.black());
+            assert_eq!((board.black() & stable.0, board.white() & stable.1), stable);
+            if board.legal_moves() == 0 {
+                board = board.pass();
+                continue;
+            }
+            let moves: Vec<_> = board.moves().collect();
+            board = board.play(*rng.choose(&moves).unwrap()).unwrap();
+        }
+        assert_eq!((board.black() & stable.0, board.white() & stable.1), stable);
+    }
+}
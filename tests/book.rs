@@ -0,0 +1,134 @@
+//! Test module for opening books.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::ai_player::{AiKind, AiPlayer};
+use rusthello_lib::{bit_board, record};
+use rusthello_lib::book::{Book, BookMode, BookMove};
+use rusthello_lib::custom_ai::SearchConfig;
+use rusthello_lib::game::{Coord, IsPlayer, PlayerAction, Turn};
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+/// Values the moves by the disk difference right after them.
+fn evaluate(my: u64, opp: u64) -> Vec<(u64, BookMove)> {
+    let mut moves = bit_board::valid_moves_set(my, opp);
+    let mut evaluated = Vec::new();
+    while moves != 0 {
+        let disk = 1u64 << moves.trailing_zeros();
+        moves ^= disk;
+        let (my, opp) = bit_board::move_bit_board(my, opp, disk);
+        evaluated.push((disk, BookMove { visits: 0, value: my.count_ones() as f32 - opp.count_ones() as f32 }));
+    }
+    evaluated
+}
+
+/// Adds `lines` lines to the book, checking that the values are backed up along each of them.
+fn grow(book: &mut Book, lines: usize) {
+    for _ in 0 .. lines {
+        let (line, added) = book.add_line(4, 2.0, evaluate);
+        assert!(added);
+        let (mut my, mut opp) = (bit_board::START.mover(), bit_board::START.opponent());
+        for coord in line {
+            let disk = 1u64 << (coord.get_row() * 8 + coord.get_col());
+            let (_, mv) = book.moves(my, opp)
+                .into_iter()
+                .find(|&(other, _)| other == disk)
+                .expect("The moves of the line are in the book");
+            assert!(mv.visits > 0);
+            let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+            // Negamax: the move is worth the opposite of the position it leads to
+            let child = book.value(nopp, nmy).expect("The positions of the line are in the book");
+            assert_eq!(mv.value, -child);
+            my = nopp;
+            opp = nmy;
+            if bit_board::valid_moves_set(my, opp) == 0 {
+                ::std::mem::swap(&mut my, &mut opp);
+            }
+        }
+    }
+}
+
+fn read(path: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    File::open(path).and_then(|mut file| file.read_to_end(&mut bytes)).expect("The book should be read");
+    bytes
+}
+
+#[test]
+fn test_book_growth() {
+    let dir = env::temp_dir();
+    let path = dir.join("rusthello_test_book.bin");
+    let path = path.to_str().expect("The path should be valid Unicode");
+    let whole_path = dir.join("rusthello_test_whole_book.bin");
+    let whole_path = whole_path.to_str().expect("The path should be valid Unicode");
+
+    let mut book = Book::new();
+    grow(&mut book, 10);
+    assert_eq!(book.len(), 10);
+    book.save(path).expect("The book should be saved");
+    let mut book = Book::load(path).expect("The book should be loaded");
+    assert_eq!(book.len(), 10);
+
+    // Growing a reloaded book is the same as growing it at once
+    grow(&mut book, 10);
+    book.save(path).expect("The book should be saved");
+    let mut whole = Book::new();
+    grow(&mut whole, 20);
+    whole.save(whole_path).expect("The book should be saved");
+    assert_eq!(read(path), read(whole_path));
+
+    // The book plays its best move
+    let coord = whole.choose(bit_board::START).expect("The first position is in the book");
+    assert!([Coord::new(2, 3), Coord::new(3, 2), Coord::new(4, 5), Coord::new(5, 4)].contains(&coord));
+}
+
+#[test]
+fn test_invalid_book() {
+    let path = env::temp_dir().join("rusthello_test_invalid_book.bin");
+    let path = path.to_str().expect("The path should be valid Unicode");
+    let mut book = Book::new();
+    grow(&mut book, 3);
+    book.save(path).expect("The book should be saved");
+    let bytes = read(path);
+    let load = |bytes: &[u8]| {
+        File::create(path).and_then(|mut file| file.write_all(bytes)).expect("The book should be written");
+        Book::load(path)
+    };
+    assert!(load(&bytes).is_ok());
+
+    // The first move of the first position: a square out of the board, then one taken by a disk
+    let mut bad = bytes.clone();
+    bad[29] = 64;
+    assert!(load(&bad).is_err());
+    let mut my = [0u8; 8];
+    my.copy_from_slice(&bytes[12 .. 20]);
+    bad[29] = u64::from_le_bytes(my).trailing_zeros() as u8;
+    assert!(load(&bad).is_err());
+
+    assert!(load(&bytes[.. bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn test_player_books() {
+    let (my, opp) = (bit_board::START.mover(), bit_board::START.opponent());
+    // Each player only knows its own first move
+    let player = |kind, name| {
+        let coord = record::parse_coord(name).expect("The square is valid");
+        let mut book = Book::new();
+        book.mode = BookMode::Deterministic;
+        let disk = 1u64 << (coord.get_row() * 8 + coord.get_col());
+        book.set_moves(my, opp, &[(disk, BookMove { visits: 1, value: 0.0 })]);
+        let mut config = SearchConfig::default();
+        config.book = Some(Arc::new(book));
+        (AiPlayer::new(kind, config), coord)
+    };
+    for &(ref player, coord) in [player(AiKind::Weak, "f5"), player(AiKind::Custom, "d3")].iter() {
+        match player.make_move(&Turn::first_turn()) {
+            Ok(PlayerAction::Move(played)) => assert_eq!(played, coord),
+            _ => panic!("no move played"),
+        }
+    }
+}
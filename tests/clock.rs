@@ -0,0 +1,34 @@
+//! Test module for the clock of the matches.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::clock::{self, Clock};
+use rusthello_lib::game::Side;
+use std::time::Duration;
+
+#[test]
+fn test_charge() {
+    let clock = Clock::new(Duration::from_secs(60), Duration::from_secs(2));
+    assert_eq!(clock.get_increment(), Duration::from_secs(2));
+
+    // Moves earn the increment, other actions do not
+    assert!(clock.charge(Side::Dark, Duration::from_secs(10), true));
+    assert_eq!(clock.get_remaining(Side::Dark), Duration::from_secs(52));
+    assert!(clock.charge(Side::Dark, Duration::from_secs(2), false));
+    assert_eq!(clock.get_remaining(Side::Dark), Duration::from_secs(50));
+    // Each side has its own time
+    assert_eq!(clock.get_remaining(Side::Light), Duration::from_secs(60));
+
+    // Running out of time, even exactly, loses and leaves no time
+    assert!(!clock.charge(Side::Light, Duration::from_secs(60), true));
+    assert_eq!(clock.get_remaining(Side::Light), Duration::from_secs(0));
+    assert!(!clock.charge(Side::Dark, Duration::from_secs(75), true));
+    assert_eq!(clock.get_remaining(Side::Dark), Duration::from_secs(0));
+}
+
+#[test]
+fn test_format_time() {
+    assert_eq!(clock::format_time(Duration::from_millis(247_900)), "4:07");
+    assert_eq!(clock::format_time(Duration::from_secs(3600)), "60:00");
+    assert_eq!(clock::format_time(Duration::from_secs(0)), "0:00");
+}
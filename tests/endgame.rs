@@ -0,0 +1,149 @@
+//! Test module for the endgame solver.
+
+extern crate rand;
+extern crate rusthello_lib;
+
+use rand::Rng;
+use rusthello_lib::ai_player::Score;
+use rusthello_lib::bit_board;
+use rusthello_lib::bit_board::BitBoard;
+use rusthello_lib::custom_ai::{self, Deadline, EndgameOptions, SearchConfig, SearchContext, SilentObserver, SolveMode,
+                               SolveScore};
+use rusthello_lib::transposition::TranspositionTable;
+
+/// Positions of random games with `empties` empty cells, as `(my, opp)` of the side to move.
+fn random_positions(count: usize, empties: u32) -> Vec<(u64, u64)> {
+    let mut rng = rand::thread_rng();
+    let mut positions = Vec::new();
+    while positions.len() < count {
+        let mut board = bit_board::START;
+        while board.empties() > empties && !board.is_game_over() {
+            if board.legal_moves() == 0 {
+                board = board.pass();
+                continue;
+            }
+            let moves: Vec<_> = board.moves().collect();
+            board = board.play(*rng.choose(&moves).unwrap()).unwrap();
+        }
+        if board.empties() == empties && board.legal_moves() != 0 {
+            positions.push((board.mover(), board.opponent()));
+        }
+    }
+    positions
+}
+
+/// The final disk difference for the side to move, by a plain alpha-beta search.
+fn solve(my: u64, opp: u64, mut alpha: i16, beta: i16) -> i16 {
+    let mut moves = bit_board::valid_moves_set(my, opp);
+    if moves == 0 {
+        return if bit_board::valid_moves_set(opp, my) == 0 {
+            my.count_ones() as i16 - opp.count_ones() as i16
+        } else {
+            -solve(opp, my, -beta, -alpha)
+        };
+    }
+    while moves != 0 {
+        let disk = 1u64 << moves.trailing_zeros();
+        moves ^= disk;
+        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+        alpha = ::std::cmp::max(alpha, -solve(nopp, nmy, -beta, -alpha));
+        if alpha >= beta {
+            break;
+        }
+    }
+    alpha
+}
+
+/// Checks the scores of the solver with the given enhancements against a plain search:
+/// the enhancements change the nodes searched, not the scores.
+fn check_solver(options: EndgameOptions) {
+    let mut config = SearchConfig::default();
+    config.endgame = options;
+    let tt = TranspositionTable::new(1);
+    let never = Deadline::never();
+    let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &SilentObserver };
+    for (my, opp) in random_positions(4, 13) {
+        let expected = solve(my, opp, -64, 64);
+        let moves = bit_board::valid_moves_set(my, opp);
+        let mut nnodes = 0;
+        tt.clear();
+
+        // Scores are from the opponent's point of view
+        let mut moves_and_scores = Vec::new();
+        assert!(custom_ai::ai_eval_till_end(my, opp, moves, &mut moves_and_scores, false, &ctx, &mut nnodes));
+        assert_eq!(moves_and_scores.iter().map(|&(_, score)| score).min(), Some(Score::Ended(-expected)),
+                   "{:?}", options);
+
+        // Lock mode only tells whether the best move wins
+        let mut moves_and_scores = Vec::new();
+        assert!(custom_ai::ai_eval_till_end(my, opp, moves, &mut moves_and_scores, true, &ctx, &mut nnodes));
+        match moves_and_scores.iter().map(|&(_, score)| score).min() {
+            Some(Score::Ended(score)) => assert_eq!(score < 0, expected > 0, "{:?}", options),
+            other => panic!("no move found: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_endgame_solver() {
+    check_solver(EndgameOptions::all());
+    check_solver(EndgameOptions::none());
+}
+
+#[test]
+fn test_parity() {
+    check_solver(EndgameOptions { parity: true, ..EndgameOptions::none() });
+}
+
+#[test]
+fn test_last_moves() {
+    check_solver(EndgameOptions { last_moves: true, ..EndgameOptions::none() });
+}
+
+#[test]
+fn test_stability() {
+    check_solver(EndgameOptions { stability: true, ..EndgameOptions::none() });
+}
+
+#[test]
+fn test_etc() {
+    check_solver(EndgameOptions { etc: true, ..EndgameOptions::none() });
+}
+
+#[test]
+fn test_solve_modes() {
+    let config = SearchConfig::default();
+    let tt = TranspositionTable::new(1);
+    let never = Deadline::never();
+    let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &SilentObserver };
+    for (my, opp) in random_positions(4, 12) {
+        let expected = solve(my, opp, -64, 64);
+        let board = BitBoard::from_my_opp(my, opp, true);
+        let solution = custom_ai::solve(board, SolveMode::Exact, &ctx).unwrap();
+        assert_eq!(solution.score, SolveScore::Exact(expected));
+        assert!(solution.nodes > 0);
+        let best = solution.best_move.expect("There is a legal move");
+        assert_eq!(solution.principal_variation[0], best);
+        let after = board.play(best).unwrap();
+        assert_eq!(-solve(after.mover(), after.opponent(), -64, 64), expected);
+
+        tt.clear();
+        let wld = custom_ai::solve(board, SolveMode::WinLossDraw, &ctx).unwrap();
+        match wld.score {
+            SolveScore::AtLeast(score) => assert!(score > 0 && expected >= score),
+            SolveScore::AtMost(score) => assert!(score < 0 && expected <= score),
+            SolveScore::Exact(score) => assert_eq!((score, expected), (0, 0)),
+        }
+
+        // Outside of the window, only bounds
+        tt.clear();
+        match custom_ai::solve(board, SolveMode::Window(expected, expected + 4), &ctx).unwrap().score {
+            SolveScore::AtMost(score) => assert!(score >= expected),
+            other => panic!("{:?} for {}", other, expected),
+        }
+        match custom_ai::solve(board, SolveMode::Window(expected - 4, expected - 1), &ctx).unwrap().score {
+            SolveScore::AtLeast(score) => assert!(score <= expected),
+            other => panic!("{:?} for {}", other, expected),
+        }
+    }
+}
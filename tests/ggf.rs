@@ -0,0 +1,72 @@
+//! Test module for GGF games.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::ggf;
+use rusthello_lib::game::Side;
+use rusthello_lib::ggf::GgfGame;
+use rusthello_lib::record::GameRecord;
+use std::time::Duration;
+
+const GAME: &str = "(;GM[Othello]PC[RUSThello]DT[2017.05.21]PB[Weak AI]PW[Weak AI]RE[-42.000]TI[1:00/0:01/0:00]TY[8]BO[8 ---------------------------O*------*O--------------------------- *]B[C4/1.50/0.01]W[E3//0.00]B[F4//0.00]W[G5//0.00]B[F2//0.00]W[E2//0.00]B[G4//0.00]W[G3//0.00]B[E1//0.00]W[B4//0.00]B[E6//0.00]W[F6//0.00]B[G6//0.01]W[E7//0.01]B[H6//0.00]W[C5//0.00]B[D8//0.00]W[E8//0.00]B[F8//0.00]W[H4//0.00]B[D6//0.00]W[D7//0.00]B[D3//0.00]W[F1//0.00]B[A3//0.00]W[F3//0.00]B[C3//0.00]W[C6//0.01]B[B6//0.00]W[D1//0.00]B[H3//0.00]W[F5//0.00]B[H5//0.00]W[F7//0.00]B[G7//0.00]W[D2//0.00]B[C1//0.00]W[C2//0.00]B[G1//0.00]W[B3//0.00]B[B2//0.00]W[A4//0.00]B[A5//0.00]W[H8//0.00]B[G8//0.00]W[A1//0.00]B[A2//0.00]W[B1//0.00]B[B5//0.00]W[H7//0.00]B[G2//0.00]W[H1//0.00]B[PA]W[C8//0.00]B[C7//0.00]W[B8//0.00]B[B7//0.00]W[H2//0.00]B[PA]W[A8//0.00]B[A7//0.00]W[A6//0.00];)
+";
+
+#[test]
+fn test_ggf_round_trip() {
+    let game: GgfGame = GAME.parse().expect("The game should be valid");
+    assert_eq!(game.black, "Weak AI");
+    assert_eq!(game.result, "-42.000");
+    assert_eq!(game.moves.len(), 62);
+    assert_eq!(game.moves[0].eval, Some(1.5));
+    assert_eq!(game.moves[0].time, Some(0.01));
+    assert_eq!(game.to_string(), GAME);
+
+    let record = game.to_record().expect("The game should start from the usual position");
+    assert_eq!(record.moves.len(), 60);
+    assert_eq!(record.result, "11-53");
+    assert_eq!(record.evals[0], Some(1.5));
+    assert!(record.evals[1 ..].iter().all(Option::is_none));
+
+    // Evaluations survive the way back
+    let times: Vec<Option<f64>> = game.moves.iter().filter(|mv| mv.coord.is_some()).map(|mv| mv.time).collect();
+    let back = GgfGame::from_record(&record, &times, &game.time_control).expect("The record should be valid");
+    assert_eq!(back.moves, game.moves);
+}
+
+#[test]
+fn test_ggf_time_loss() {
+    // Light, to move, ran out of time
+    let mut record: GameRecord = "f5d6c3".parse().expect("The transcript should be valid");
+    record.set_time_loss(Side::Light);
+    assert_eq!(record.time_loser(), Some(Side::Light));
+    let reloaded: GameRecord = record.to_string().parse().expect("The record should be valid");
+    assert_eq!(reloaded.result, record.result);
+
+    let game = GgfGame::from_record(&record, &[], "").expect("The record should be valid");
+    assert_eq!(game.result, "+64.000:t");
+    let back = game.to_record().expect("The game should start from the usual position");
+    assert_eq!(back.result, record.result);
+}
+
+#[test]
+fn test_ggf_illegal_move() {
+    assert!("(;GM[Othello]TY[8]B[F5]W[D6];)".parse::<GgfGame>().is_ok());
+    assert!("(;GM[Othello]TY[8]W[F5];)".parse::<GgfGame>().is_err());
+    assert!("(;GM[Othello]TY[8]B[F5]W[PA];)".parse::<GgfGame>().is_err());
+    assert!("(;GM[Othello]TY[10]B[F5];)".parse::<GgfGame>().is_err());
+}
+
+#[test]
+fn test_ggf_clock() {
+    assert_eq!(ggf::parse_time_control("5:00/0:03/0:00"), Some((Duration::from_secs(300), Duration::from_secs(3))));
+    assert_eq!(ggf::parse_time_control("1:30"), Some((Duration::from_secs(90), Duration::from_secs(0))));
+    assert_eq!(ggf::parse_time_control(""), None);
+
+    // Black, to move, spent 0.02 seconds on its 31 moves, each adding a second
+    let mut game: GgfGame = GAME.parse().expect("The game should be valid");
+    let (remaining, increment) = game.clock().expect("The game has a clock");
+    assert!((remaining.as_secs_f64() - 90.98).abs() < 1e-6);
+    assert_eq!(increment, Duration::from_secs(1));
+    game.time_control.clear();
+    assert!(game.clock().is_none());
+}
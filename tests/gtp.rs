@@ -0,0 +1,93 @@
+//! Test module for the GTP engine.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::ai_player::TimeControl;
+use rusthello_lib::gtp::Engine;
+use std::time::Duration;
+
+fn run(engine: &mut Engine, line: &str) -> Result<String, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (response, quit) = engine.execute(words[0], &words[1 ..]);
+    assert!(!quit);
+    response
+}
+
+#[test]
+fn test_gtp_session() {
+    let mut engine = Engine::default();
+    assert_eq!(run(&mut engine, "boardsize 8"), Ok(String::new()));
+    assert!(run(&mut engine, "boardsize 19").is_err());
+    assert!(run(&mut engine, "boardsize").is_err());
+    assert_eq!(run(&mut engine, "known_command genmove"), Ok("true".to_string()));
+    assert_eq!(run(&mut engine, "known_command fly"), Ok("false".to_string()));
+
+    assert_eq!(run(&mut engine, "play black f5"), Ok(String::new()));
+    assert!(run(&mut engine, "play black d6").is_err());
+    assert!(run(&mut engine, "play white a1").is_err());
+    assert!(run(&mut engine, "play white pass").is_err());
+    assert_eq!(run(&mut engine, "rusthello-ai weak"), Ok(String::new()));
+    let reply = run(&mut engine, "genmove white").expect("White has moves");
+    assert!(["D6", "F6", "F4"].contains(&&*reply));
+    assert!(run(&mut engine, "genmove white").is_err());
+
+    // Undoing goes back one move at a time, down to the first turn
+    assert_eq!(run(&mut engine, "undo"), Ok(String::new()));
+    assert!(run(&mut engine, "play black d6").is_err());
+    assert_eq!(run(&mut engine, "play white d6"), Ok(String::new()));
+    assert_eq!(run(&mut engine, "undo"), Ok(String::new()));
+    assert_eq!(run(&mut engine, "undo"), Ok(String::new()));
+    assert!(run(&mut engine, "undo").is_err());
+
+    assert_eq!(run(&mut engine, "play black f5"), Ok(String::new()));
+    assert_eq!(run(&mut engine, "clear_board"), Ok(String::new()));
+    assert_eq!(run(&mut engine, "play black d3"), Ok(String::new()));
+}
+
+#[test]
+fn test_gtp_time() {
+    let mut engine = Engine::default();
+    assert!(run(&mut engine, "time_settings 60 30").is_err());
+    assert!(engine.time_control().is_none());
+
+    // A byo-yomi of 30 seconds for 5 stones is an increment of 6 seconds
+    assert_eq!(run(&mut engine, "time_settings 60 30 5"), Ok(String::new()));
+    match engine.time_control() {
+        Some(&TimeControl::Game { ref remaining, increment }) => {
+            assert_eq!(remaining.get(), Duration::from_secs(60));
+            assert_eq!(increment, Duration::from_secs(6));
+        }
+        _ => panic!("The AI should play on a game clock"),
+    }
+
+    // Only the time of the side to move is taken into account
+    assert_eq!(run(&mut engine, "time_left white 10 0"), Ok(String::new()));
+    match engine.time_control() {
+        Some(TimeControl::Game { remaining, .. }) => assert_eq!(remaining.get(), Duration::from_secs(60)),
+        _ => panic!("The AI should play on a game clock"),
+    }
+    assert_eq!(run(&mut engine, "time_left black 42.5 0"), Ok(String::new()));
+    match engine.time_control() {
+        Some(&TimeControl::Game { ref remaining, increment }) => {
+            assert_eq!(remaining.get(), Duration::from_millis(42_500));
+            assert_eq!(increment, Duration::from_secs(6));
+        }
+        _ => panic!("The AI should play on a game clock"),
+    }
+
+    // In byo-yomi, the time left is shared among the stones left
+    assert_eq!(run(&mut engine, "time_left black 20 4"), Ok(String::new()));
+    match engine.time_control() {
+        Some(&TimeControl::PerMove(time)) => assert_eq!(time, Duration::from_secs(5)),
+        _ => panic!("The AI should play in byo-yomi"),
+    }
+
+    // Without main time, only the byo-yomi is left
+    assert_eq!(run(&mut engine, "time_settings 0 10 2"), Ok(String::new()));
+    match engine.time_control() {
+        Some(&TimeControl::PerMove(time)) => assert_eq!(time, Duration::from_secs(5)),
+        _ => panic!("The AI should play in byo-yomi"),
+    }
+    assert_eq!(run(&mut engine, "time_settings 0 0 0"), Ok(String::new()));
+    assert!(engine.time_control().is_none());
+}
@@ -0,0 +1,67 @@
+//! Test module for the NBoard engine.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::ggf;
+use rusthello_lib::ggf::GgfGame;
+use rusthello_lib::nboard::Engine;
+use rusthello_lib::record;
+use std::time::{Duration, Instant};
+
+const GAME: &str = "(;GM[Othello]PC[NBoard]PB[Human]PW[RUSThello]RE[?]TI[0:01//0:00]TY[8]\
+                    BO[8 ---------------------------O*------*O--------------------------- *]B[F5];)";
+
+fn run(engine: &mut Engine, line: &str) -> String {
+    let mut output = Vec::new();
+    assert!(engine.execute(line, &mut output).expect("Writing to memory never fails"));
+    String::from_utf8(output).expect("The replies should be text")
+}
+
+#[test]
+fn test_nboard_session() {
+    let mut engine = Engine::default();
+    assert_eq!(run(&mut engine, "nboard 2"), "set myname RUSThello\n");
+    assert_eq!(run(&mut engine, "set depth 3"), "");
+    assert_eq!(run(&mut engine, &format!("set game {}", GAME.replace("TI[0:01//0:00]", ""))), "");
+    assert_eq!(run(&mut engine, "move D6//0.5"), "");
+    assert_eq!(run(&mut engine, "move A1"), "status Illegal move A1\n");
+    assert_eq!(run(&mut engine, "ping 7"), "pong 7\n");
+
+    let reply = run(&mut engine, "go");
+    let lines: Vec<&str> = reply.lines().collect();
+    assert_eq!(lines.first(), Some(&"status Thinking"));
+    assert_eq!(lines.last(), Some(&"status"));
+    let answer = lines[lines.len() - 2];
+    assert!(answer.starts_with("=== "));
+    let mv = ggf::parse_move(&answer[4 ..]).expect("The answer should be a move");
+    let mut game: GgfGame = GAME.parse().expect("The game should be valid");
+    game.moves.push(ggf::parse_move("D6").unwrap());
+    let board = *game.positions().expect("The moves should be legal").last().unwrap();
+    assert!(record::play(board, mv.coord.expect("Black has moves")).is_some());
+    // Evaluations are in disks
+    assert!(mv.eval.expect("The move should be evaluated").abs() <= 64.0);
+
+    // The analysis gives each depth its lines, the same every time
+    let hints = run(&mut engine, "hint 2");
+    let searches: Vec<&str> = hints.lines().filter(|line| line.starts_with("search ")).collect();
+    assert_eq!(searches.len(), 2 * 3);
+    for (n, line) in searches.iter().enumerate() {
+        let fields: Vec<&str> = line.split(' ').collect();
+        assert_eq!(fields[4], (n / 2 + 1).to_string());
+        assert!(fields[2].parse::<f64>().expect("The evaluation should be a number").abs() <= 64.0);
+    }
+    assert_eq!(run(&mut engine, "hint 2"), hints);
+    assert!(!engine.execute("quit", &mut Vec::new()).unwrap());
+}
+
+#[test]
+fn test_nboard_clock() {
+    // A second for the whole game leaves little for each move, however deep the search may go
+    let mut engine = Engine::default();
+    run(&mut engine, "set depth 20");
+    run(&mut engine, &format!("set game {}", GAME));
+    let start = Instant::now();
+    let reply = run(&mut engine, "go");
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(reply.lines().any(|line| line.starts_with("=== ")));
+}
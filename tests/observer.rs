@@ -0,0 +1,127 @@
+//! Test module for the observers of the searches.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::ai_player::{AiKind, AiPlayer, Score};
+use rusthello_lib::game::{IsPlayer, Turn};
+use rusthello_lib::custom_ai::{self, Deadline, RootMove, SearchConfig, SearchContext, SearchInfo, SearchLimit,
+                               SearchObserver, SilentObserver};
+use rusthello_lib::record::GameRecord;
+use rusthello_lib::transposition::TranspositionTable;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+enum Event {
+    Started(usize, bool),
+    NewBest(usize, bool, RootMove),
+    Finished(SearchInfo),
+}
+
+struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+impl SearchObserver for Recorder {
+    fn depth_started(&self, depth: usize, solving: bool) {
+        self.0.lock().unwrap().push(Event::Started(depth, solving));
+    }
+
+    fn new_best_move(&self, depth: usize, solving: bool, best: &RootMove) {
+        self.0.lock().unwrap().push(Event::NewBest(depth, solving, best.clone()));
+    }
+
+    fn depth_finished(&self, info: &SearchInfo) {
+        self.0.lock().unwrap().push(Event::Finished(info.clone()));
+    }
+}
+
+/// Checks that every iteration reports in order, and returns what the iterations found.
+fn check_events(events: &[Event], solving: bool) -> Vec<SearchInfo> {
+    let mut infos = Vec::new();
+    let mut current = None;
+    for event in events.iter() {
+        match *event {
+            Event::Started(depth, s) => {
+                assert!(current.is_none() && s == solving);
+                current = Some(depth);
+            }
+            Event::NewBest(depth, s, ref best) => {
+                assert!(current == Some(depth) && s == solving);
+                assert_eq!(best.line.first(), Some(&best.coord));
+            }
+            Event::Finished(ref info) => {
+                assert!(current == Some(info.depth) && info.solving == solving);
+                assert!(!info.moves.is_empty());
+                // Best first
+                assert!(info.moves.windows(2).all(|pair| pair[0].score >= pair[1].score));
+                current = None;
+                infos.push(info.clone());
+            }
+        }
+    }
+    assert!(current.is_none());
+    infos
+}
+
+#[test]
+fn test_search_observer() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Recorder(events.clone());
+
+    // Midgame, by iterative deepening
+    let record: GameRecord = "f5d6c3d3c4f4f6f3e6e7".parse().expect("The transcript should be valid");
+    let board = *record.positions().expect("The moves should be legal").last().unwrap();
+    let config = SearchConfig::default();
+    let tt = TranspositionTable::new(1);
+    let best = custom_ai::find_best_move_with_table(board, SearchLimit::Depth(3), &config, &tt, &recorder);
+    let infos = check_events(&events.lock().unwrap(), false);
+    assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(infos[2].best_move().map(|best| best.coord), best);
+    assert!(infos.iter().all(|info| info.moves.len() == board.legal_moves().count_ones() as usize));
+    events.lock().unwrap().clear();
+
+    // Endgame, solved
+    let mut board = board;
+    while board.empties() > 12 || board.legal_moves() == 0 {
+        board = match board.moves().next() {
+            Some(coord) => board.play(coord).unwrap(),
+            None => board.pass(),
+        };
+    }
+    let (my, opp) = (board.mover(), board.opponent());
+    let mut moves_and_scores = Vec::new();
+    let mut nnodes = 0;
+    let never = Deadline::never();
+    let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &recorder };
+    assert!(custom_ai::ai_eval_till_end(my, opp, board.legal_moves(), &mut moves_and_scores, false,
+                                        &ctx, &mut nnodes));
+    let infos = check_events(&events.lock().unwrap(), true);
+    assert_eq!(infos.len(), 1);
+    assert_eq!(infos[0].depth, board.empties() as usize);
+    assert_eq!(infos[0].nodes, nnodes);
+    // Scores are for the side to move, unlike those of `ai_eval_till_end`
+    let best = moves_and_scores.iter().min_by_key(|&&(_, score)| score).unwrap();
+    let expected = match best.1 {
+        Score::Ended(diff) => Score::Ended(-diff),
+        score => panic!("The endgame is solved, not {:?}", score),
+    };
+    assert_eq!(infos[0].best_move().map(|best| best.score), Some(expected));
+    events.lock().unwrap().clear();
+
+    // Other searches are not followed
+    custom_ai::find_best_move_with_table(board, SearchLimit::Depth(3), &config, &tt, &SilentObserver);
+    assert!(events.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_player_observer() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut followed = AiPlayer::new(AiKind::Custom, SearchConfig::default());
+    followed.observer = Box::new(Recorder(events.clone()));
+    let silent = AiPlayer::new(AiKind::Custom, SearchConfig::default());
+    let turn = Turn::first_turn();
+
+    silent.make_move(&turn).expect("There is a legal move");
+    assert!(events.lock().unwrap().is_empty());
+    followed.make_move(&turn).expect("There is a legal move");
+    let infos = check_events(&events.lock().unwrap(), false);
+    assert_eq!(infos.len(), custom_ai::USUAL_DEPTH);
+}
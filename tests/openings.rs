@@ -0,0 +1,32 @@
+//! Test module for opening names.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::openings;
+use rusthello_lib::record::GameRecord;
+
+fn opening(transcript: &str) -> Option<&'static str> {
+    let record: GameRecord = transcript.parse().expect("The transcript should be valid");
+    openings::opening_name(*record.positions().expect("The moves should be legal").last().unwrap())
+}
+
+#[test]
+fn test_opening_names() {
+    assert_eq!(opening("f5d6c3d3c4"), Some("Tiger"));
+    // The same moves on a transposed board
+    assert_eq!(opening("e6f4c3c4d3"), Some("Tiger"));
+    // Still on the line of the Mainline Tiger, but not there yet
+    assert_eq!(opening("f5d6c3d3c4f4f6f3e6"), Some("Tiger"));
+    assert_eq!(opening("f5d6c3d3c4f4f6f3e6e7d7"), Some("Mainline Tiger"));
+    // Out of the named lines
+    assert_eq!(opening("f5d6c3d3c4b5"), None);
+}
+
+#[test]
+fn test_game_opening() {
+    let mut record: GameRecord = "f5d6c3d3c4b5".parse().expect("The transcript should be valid");
+    record.set_opening();
+    assert_eq!(record.opening, Some("Tiger".to_string()));
+    let saved: GameRecord = record.to_string().parse().expect("The record should be valid");
+    assert_eq!(saved.opening, Some("Tiger".to_string()));
+}
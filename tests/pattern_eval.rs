@@ -0,0 +1,108 @@
+//! Test module for the pattern-based evaluation.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::bit_board;
+use rusthello_lib::bit_board::SYMMETRIES;
+use rusthello_lib::pattern_eval::{PatternWeights, Sample, PHASES};
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+
+/// Weights all different from each other, but always the same.
+fn some_weights() -> PatternWeights {
+    let mut weights = PatternWeights::zeros();
+    for (n, weight) in weights.weights_mut().iter_mut().enumerate() {
+        *weight = (n * 7919 % 1000) as f32 / 100.0 - 5.0;
+    }
+    weights
+}
+
+/// Positions from all the phases of a game.
+fn positions() -> Vec<(u64, u64)> {
+    let mut positions = Vec::new();
+    let mut board = bit_board::START;
+    while !board.is_game_over() {
+        positions.push((board.mover(), board.opponent()));
+        board = match board.moves().last() {
+            Some(coord) => board.play(coord).expect("The move should be legal"),
+            None => board.pass(),
+        };
+    }
+    positions
+}
+
+#[test]
+fn test_eval_features() {
+    let mut weights = some_weights();
+    let mut features = Vec::new();
+    for (my, opp) in positions() {
+        for &symmetry in SYMMETRIES.iter() {
+            let (my, opp) = (symmetry.apply(my), symmetry.apply(opp));
+            weights.features(my, opp, &mut features);
+            let sum: f64 = features.iter()
+                .map(|&(index, value)| weights.weights_mut()[index] as f64 * value as f64)
+                .sum();
+            assert!((weights.eval(my, opp) - sum).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn test_save_and_load() {
+    let weights = some_weights();
+    let path = env::temp_dir().join("rusthello_test_weights.bin");
+    let path = path.to_str().expect("The path should be valid Unicode");
+    weights.save(path).expect("The weights should be saved");
+    let loaded = PatternWeights::load(path).expect("The weights should be loaded");
+    for (my, opp) in positions() {
+        assert_eq!(loaded.eval(my, opp), weights.eval(my, opp));
+    }
+
+    // A file of version 1 is the same without the mobility weights
+    let mut bytes = Vec::new();
+    File::open(path).and_then(|mut file| file.read_to_end(&mut bytes)).expect("The file should be read");
+    let mut header = [0u8; 4];
+    header.copy_from_slice(&bytes[12 .. 16]);
+    let phase_len = u32::from_le_bytes(header) as usize;
+    let mut old = bytes[.. 16].to_vec();
+    old[4] = 1;
+    old[12 .. 16].copy_from_slice(&(phase_len as u32 - 1).to_le_bytes());
+    for phase in 0 .. PHASES {
+        let start = 16 + 4 * phase * phase_len;
+        old.extend_from_slice(&bytes[start .. start + 4 * (phase_len - 2)]);
+        old.extend_from_slice(&bytes[start + 4 * (phase_len - 1) .. start + 4 * phase_len]);
+    }
+    File::create(path).and_then(|mut file| file.write_all(&old)).expect("The file should be written");
+    let loaded = PatternWeights::load(path).expect("The weights of version 1 should be loaded");
+    let mut expected = some_weights();
+    for phase in 0 .. PHASES {
+        expected.weights_mut()[phase * phase_len + phase_len - 2] = 0.0;
+    }
+    for (my, opp) in positions() {
+        assert_eq!(loaded.eval(my, opp), expected.eval(my, opp));
+    }
+
+    old.pop();
+    File::create(path).and_then(|mut file| file.write_all(&old)).expect("The file should be written");
+    assert!(PatternWeights::load(path).is_err());
+}
+
+#[test]
+fn test_descend() {
+    // The evaluation is linear in the weights, so a target made by other weights can be fitted
+    let target = some_weights();
+    let samples: Vec<Sample> = positions()
+        .into_iter()
+        .map(|(my, opp)| Sample { my, opp, diff: target.eval(my, opp) as f32 })
+        .collect();
+    let mut weights = PatternWeights::zeros();
+    let first = weights.descend(&samples, 0.002);
+    let mut last = first;
+    for _ in 0 .. 20 {
+        let rmse = weights.descend(&samples, 0.002);
+        assert!(rmse <= last);
+        last = rmse;
+    }
+    assert!(last < first / 2.0);
+}
@@ -0,0 +1,49 @@
+//! Test module for move generation.
+
+#[cfg(feature = "reversi")]
+extern crate reversi;
+extern crate rusthello_lib;
+
+use rusthello_lib::bit_board;
+#[cfg(feature = "reversi")]
+use rusthello_lib::bit_board::BitBoard;
+use rusthello_lib::game::Turn;
+use rusthello_lib::perft::{self, REFERENCE};
+
+#[test]
+fn test_perft() {
+    // Deep enough for the first passes and the first finished games
+    for (depth, &count) in REFERENCE.iter().enumerate().take(10) {
+        assert_eq!(perft::perft_bit_board(bit_board::START, depth), count);
+    }
+    for (depth, &count) in REFERENCE.iter().enumerate().take(6) {
+        assert_eq!(perft::perft_turn(&Turn::first_turn(), depth).expect("Legal moves can be played"), count);
+    }
+}
+
+#[cfg(feature = "reversi")]
+#[test]
+fn test_perft_reversi() {
+    for (depth, &count) in REFERENCE.iter().enumerate().take(6) {
+        assert_eq!(perft::perft_reversi(&reversi::turn::Turn::first_turn(), depth).expect("Legal moves can be played"),
+                   count);
+    }
+}
+
+/// The two game models agree on the positions of a game.
+#[cfg(feature = "reversi")]
+#[test]
+fn test_reversi_conversion() {
+    let mut turn = reversi::turn::Turn::first_turn();
+    let mut board = bit_board::START;
+    assert_eq!(BitBoard::from(&turn), board);
+    for &(row, col) in [(4, 5), (5, 3), (4, 2), (3, 5), (6, 4), (5, 5), (4, 6), (5, 4), (2, 4)].iter() {
+        turn.make_move(reversi::board::Coord::new(row, col)).expect("The move is legal");
+        board = board.play(rusthello_lib::game::Coord::new(row, col)).expect("The move is legal");
+        let converted = BitBoard::from(&turn);
+        assert_eq!((converted.black(), converted.white()), (board.black(), board.white()));
+        if !turn.is_end_state() {
+            assert_eq!(converted, board);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Test module for game records.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::record::GameRecord;
+
+const GAME: &str = "[Dark \"Weak AI\"]
+[Light \"Weak AI\"]
+[Date \"2017.05.21\"]
+[Result \"58-6\"]
+c4e3f4g5e2e1g4c5c6b5h6g3a6a5a4g6f6e7e6d6f5f7c3b6c7b4h3c8f8d2d3f3f1f2d1c1d7d8a3h4c2b3e8h2h5h7b1g8g1b7a8a7b8a2h8g2h1paa1pab2pag7
+";
+
+#[test]
+fn test_record_round_trip() {
+    let record: GameRecord = GAME.parse().expect("The record should be valid");
+    assert_eq!(record.dark, "Weak AI");
+    assert_eq!(record.result, "58-6");
+    assert_eq!(record.moves.len(), 60);
+    assert_eq!(record.to_string(), GAME);
+
+    // Passes can be left out
+    let record: GameRecord = GAME.replace("pa", "").parse().expect("The record should be valid");
+    assert_eq!(record.to_string(), GAME);
+}
+
+#[test]
+fn test_record_illegal_move() {
+    assert!("f5d6c3".parse::<GameRecord>().is_ok());
+    assert!("f5a1".parse::<GameRecord>().is_err());
+    assert!("f5z9".parse::<GameRecord>().is_err());
+}
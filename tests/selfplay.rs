@@ -0,0 +1,94 @@
+//! Test module for the statistics of self-play.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::book::BookMode;
+use rusthello_lib::selfplay::{self, PlayerKind, PlayerSpec, Sprt, Stats};
+use std::env;
+
+fn stats(wins: u32, draws: u32, losses: u32) -> Stats {
+    Stats { wins, draws, losses, disk_diff: 0 }
+}
+
+#[test]
+fn test_elo() {
+    assert_eq!(stats(0, 0, 0).elo(), (0.0, f64::INFINITY));
+    let (elo, margin) = stats(10, 0, 10).elo();
+    assert!(elo.abs() < 1e-9 && margin > 0.0);
+    // 3 points out of 4 is about 191 Elo
+    let (elo, _) = stats(3, 0, 1).elo();
+    assert!((elo - 190.85).abs() < 0.01);
+    assert!((selfplay::score_from_elo(elo) - 0.75).abs() < 1e-9);
+
+    // A perfect or a null score gives a finite Elo, clamped at a score of 0.1%
+    let (best, margin) = stats(20, 0, 0).elo();
+    assert!((best - 1199.83).abs() < 0.01);
+    assert_eq!(margin, 0.0);
+    let (worst, _) = stats(0, 0, 20).elo();
+    assert!((worst + best).abs() < 1e-9);
+    assert_eq!(selfplay::elo_from_score(1.5), best);
+    assert_eq!(selfplay::elo_from_score(-0.5), worst);
+}
+
+#[test]
+fn test_sprt() {
+    let sprt = Sprt::new(0.0, 50.0);
+    let (lower, upper) = sprt.bounds();
+    assert!((lower + 2.944439).abs() < 1e-6 && (upper - 2.944439).abs() < 1e-6);
+
+    // No games, or no variance, give no evidence either way
+    assert_eq!(sprt.llr(&stats(0, 0, 0)), 0.0);
+    assert_eq!(sprt.llr(&stats(0, 5, 0)), 0.0);
+    assert_eq!(sprt.decision(&stats(0, 5, 0)), None);
+
+    assert!((sprt.llr(&stats(60, 20, 20)) - 7.336959).abs() < 1e-6);
+    assert_eq!(sprt.decision(&stats(60, 20, 20)), Some(true));
+    assert!((sprt.llr(&stats(45, 10, 45)) + 1.134884).abs() < 1e-6);
+    assert_eq!(sprt.decision(&stats(45, 10, 45)), None);
+    // Ten times the same games are ten times the evidence
+    assert!((sprt.llr(&stats(450, 100, 450)) + 11.348838).abs() < 1e-6);
+    assert_eq!(sprt.decision(&stats(450, 100, 450)), Some(false));
+}
+
+#[test]
+fn test_player_spec() {
+    let player: PlayerSpec = "Custom:3,weights=Net.bin,book=openings.bin".parse().expect("The player should be valid");
+    assert_eq!(player.kind, PlayerKind::Custom(3));
+    // File names keep their case
+    assert_eq!(player.weights, Some("Net.bin".to_string()));
+    assert_eq!(player.book, Some("openings.bin".to_string()));
+    assert_eq!(player.to_string(), "custom:3,weights=Net.bin,book=openings.bin");
+
+    let player: PlayerSpec = "weak".parse().expect("The player should be valid");
+    assert_eq!((player.weights, player.book), (None, None));
+    let mut player: PlayerSpec = "strong,book=missing.bin".parse().expect("The player should be valid");
+    assert!(player.weights.is_none());
+    assert!(player.load(60, BookMode::Deterministic).is_err());
+
+    for text in ["custom,weights", "custom,weights=", "custom,depth=3", "weak,"].iter() {
+        assert!(text.parse::<PlayerSpec>().is_err(), "{} should be invalid", text);
+    }
+}
+
+/// The player described by the environment variable `var`, or by `default`.
+fn player(var: &str, default: &str) -> PlayerSpec {
+    let mut player: PlayerSpec = env::var(var).unwrap_or_else(|_| default.to_string())
+        .parse()
+        .expect("The player should be valid");
+    player.load(60, BookMode::WeightedRandom).expect("The files of the player should be loaded");
+    player
+}
+
+/// Tests a candidate against a baseline as `tournament --sprt 0 50` does, e.g. with
+/// `RUSTHELLO_CANDIDATE=custom,weights=new.bin RUSTHELLO_BASELINE=custom cargo test --release -- --ignored`.
+#[test]
+#[ignore]
+fn test_sprt_candidate() {
+    let candidate = player("RUSTHELLO_CANDIDATE", "custom:4");
+    let baseline = player("RUSTHELLO_BASELINE", "custom:2");
+    let sprt = Sprt::new(0.0, 50.0);
+    let openings = selfplay::balanced_openings(6, 10);
+    let (decision, stats) = selfplay::run_sprt(&candidate, &baseline, &openings, &sprt, 1000, |_| {})
+        .expect("Self-play games should end without errors");
+    assert_eq!(decision, Some(true), "the candidate scored +{} ={} -{}", stats.wins, stats.draws, stats.losses);
+}
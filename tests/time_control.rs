@@ -0,0 +1,51 @@
+//! Test module for the timed AI.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::ai_player::{AiKind, AiPlayer, TimeControl};
+use rusthello_lib::custom_ai::SearchConfig;
+use rusthello_lib::game::{Coord, IsPlayer, PlayerAction, Turn, BOARD_SIZE};
+use std::time::{Duration, Instant};
+
+/// The turn after the moves of `transcript` (e.g. "f5d6").
+fn turn_after(transcript: &str) -> Turn {
+    let mut turn = Turn::first_turn();
+    for mv in transcript.as_bytes().chunks(2) {
+        let coord = Coord::new((mv[1] - b'1') as usize, (mv[0] - b'a') as usize);
+        turn.make_move(coord).expect("The moves should be legal");
+    }
+    turn
+}
+
+/// Some legal move of `turn`.
+fn any_move(turn: &Turn) -> Coord {
+    (0 .. BOARD_SIZE * BOARD_SIZE)
+        .map(|idx| Coord::new(idx / BOARD_SIZE, idx % BOARD_SIZE))
+        .find(|&coord| turn.check_move(coord).is_ok())
+        .expect("The game is not over")
+}
+
+#[test]
+fn test_per_move_budget() {
+    let budget = Duration::from_millis(200);
+    let midgame = turn_after("f5d6c3d3c4f4f6f3e6e7");
+    // Late enough for the endgame solver, which cannot finish in time
+    let mut endgame = midgame;
+    while {
+        let (dark, light) = endgame.get_score();
+        64 - dark - light > 20
+    } {
+        let coord = any_move(&endgame);
+        endgame.make_move(coord).expect("The move is legal");
+    }
+    for turn in [midgame, endgame].iter() {
+        let ai = AiPlayer::new(AiKind::Timed(TimeControl::PerMove(budget)), SearchConfig::default());
+        let start = Instant::now();
+        match ai.make_move(turn) {
+            Ok(PlayerAction::Move(coord)) => assert!(turn.check_move(coord).is_ok()),
+            _ => panic!("The AI should move"),
+        }
+        // The search notices the deadline soon after it passes
+        assert!(start.elapsed() < budget + Duration::from_millis(300), "{:?} spent", start.elapsed());
+    }
+}
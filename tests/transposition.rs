@@ -0,0 +1,233 @@
+//! Test module for the transposition table and the searches sharing it.
+
+extern crate rusthello_lib;
+
+use rusthello_lib::ai_player::Score;
+use rusthello_lib::bit_board;
+use rusthello_lib::custom_ai::{self, Deadline, Evaluator, SearchConfig, SearchContext, SearchInfo, SearchObserver,
+                               SilentObserver};
+use rusthello_lib::transposition::{Bound, TranspositionTable, SOLVED};
+use std::sync::Mutex;
+
+/// The starting position, Dark to move.
+const MY: u64 = 1 << 28 | 1 << 35;
+const OPP: u64 = 1 << 27 | 1 << 36;
+
+#[test]
+fn test_store_and_probe() {
+    let tt = TranspositionTable::new(1);
+    assert!(tt.probe(MY, OPP).is_none());
+
+    tt.store(MY, OPP, 7, Bound::Lower, Score::Running(-1.25), 1 << 19);
+    let entry = tt.probe(MY, OPP).expect("The entry was just stored");
+    assert_eq!((entry.depth, entry.bound, entry.score, entry.best_move),
+               (7, Bound::Lower, Score::Running(-1.25), 1 << 19));
+    // The other side to move is another position
+    assert!(tt.probe(OPP, MY).is_none());
+
+    tt.store(MY, OPP, SOLVED, Bound::Exact, Score::Ended(-12), 0);
+    let entry = tt.probe(MY, OPP).expect("The entry was just stored");
+    assert_eq!((entry.depth, entry.bound, entry.score, entry.best_move),
+               (SOLVED, Bound::Exact, Score::Ended(-12), 0));
+
+    tt.clear();
+    assert!(tt.probe(MY, OPP).is_none());
+}
+
+/// Distinct keys, filling the table several times over.
+fn keys() -> Vec<(u64, u64)> {
+    (1 .. 1 << 18).map(|n: u64| (n, n.wrapping_mul(0x9e3779b97f4a7c15) & !n)).collect()
+}
+
+#[test]
+fn test_replacement() {
+    let tt = TranspositionTable::new(1);
+    tt.store(MY, OPP, 10, Bound::Exact, Score::Running(2.0), 1 << 19);
+
+    // Shallower searches of other positions leave the deep entry alone
+    for &(key_my, key_opp) in keys().iter() {
+        tt.store(key_my, key_opp, 3, Bound::Lower, Score::Running(0.5), 0);
+    }
+    assert_eq!(tt.probe(MY, OPP).map(|entry| entry.depth), Some(10));
+
+    // A shallower search of the same position replaces it
+    tt.store(MY, OPP, 2, Bound::Upper, Score::Ended(4), 1 << 37);
+    let entry = tt.probe(MY, OPP).expect("The entry was just stored");
+    assert_eq!((entry.depth, entry.bound, entry.score, entry.best_move),
+               (2, Bound::Upper, Score::Ended(4), 1 << 37));
+
+    // Searches at least as deep replace other positions
+    tt.store(MY, OPP, 10, Bound::Exact, Score::Running(2.0), 1 << 19);
+    for &(key_my, key_opp) in keys().iter() {
+        tt.store(key_my, key_opp, 10, Bound::Lower, Score::Running(0.5), 0);
+    }
+    assert!(tt.probe(MY, OPP).is_none());
+
+    // Entries of a former search are kept, but make way for any new one
+    tt.store(MY, OPP, 10, Bound::Exact, Score::Running(2.0), 1 << 19);
+    tt.new_search();
+    assert_eq!(tt.probe(MY, OPP).map(|entry| entry.depth), Some(10));
+    for &(key_my, key_opp) in keys().iter() {
+        tt.store(key_my, key_opp, 3, Bound::Lower, Score::Running(0.5), 0);
+    }
+    assert!(tt.probe(MY, OPP).is_none());
+}
+
+/// The position after the moves of `transcript` (e.g. "f5d6"), as `(my, opp)` of the side to move.
+fn play(transcript: &str) -> (u64, u64) {
+    let (mut my, mut opp) = (MY, OPP);
+    for mv in transcript.as_bytes().chunks(2) {
+        let disk = 1u64 << ((mv[1] - b'1') * 8 + mv[0] - b'a');
+        assert!(bit_board::valid_moves_set(my, opp) & disk != 0, "The moves should be legal");
+        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+        my = nopp;
+        opp = nmy;
+    }
+    (my, opp)
+}
+
+#[test]
+fn test_lazy_smp() {
+    let mut config = SearchConfig::default();
+    config.set_threads(4);
+    let (my, opp) = play("f5d6c3d3c4f4f6f3e6e7");
+    let moves = bit_board::valid_moves_set(my, opp);
+    let tt = TranspositionTable::new(1);
+    let never = Deadline::never();
+    let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &SilentObserver };
+    let mut nnodes = 0;
+    let mut moves_and_scores = Vec::new();
+    for depth in 1 .. 6 {
+        assert!(custom_ai::ai_eval_with_depth(my, opp, depth, moves, &mut moves_and_scores, &ctx, &mut nnodes));
+    }
+    // Every move gets a score, whatever the helpers did
+    assert_eq!(moves_and_scores.len(), moves.count_ones() as usize);
+    assert!(moves_and_scores.iter()
+        .all(|&(coord, _)| moves & 1u64 << (coord.get_row() * 8 + coord.get_col()) != 0));
+    assert!(nnodes > 0);
+}
+
+/// Plain negamax to a fixed depth, passes not counting as plies.
+fn negamax(my: u64, opp: u64, depth: usize, evaluator: &Evaluator) -> Score {
+    let mut moves = bit_board::valid_moves_set(my, opp);
+    if moves == 0 && bit_board::valid_moves_set(opp, my) == 0 {
+        return Score::Ended(my.count_ones() as i16 - opp.count_ones() as i16);
+    }
+    if depth == 0 {
+        return Score::Running(evaluator.eval(my, opp));
+    }
+    if moves == 0 {
+        return negate(negamax(opp, my, depth, evaluator));
+    }
+    let mut best = None;
+    while moves != 0 {
+        let disk = 1u64 << moves.trailing_zeros();
+        moves ^= disk;
+        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+        let score = negate(negamax(nopp, nmy, depth - 1, evaluator));
+        best = Some(best.map_or(score, |best: Score| best.max(score)));
+    }
+    best.unwrap()
+}
+
+fn negate(score: Score) -> Score {
+    match score {
+        Score::Running(val) => Score::Running(-val),
+        Score::Ended(diff) => Score::Ended(-diff),
+    }
+}
+
+#[test]
+fn test_score_order() {
+    let scores = [Score::Ended(-64), Score::Ended(-1), Score::Running(-1e12), Score::Running(-0.5),
+                  Score::Ended(0), Score::Running(0.5), Score::Running(1e12), Score::Ended(1), Score::Ended(64)];
+    for (i, &lower) in scores.iter().enumerate() {
+        for &higher in scores[i + 1 ..].iter() {
+            assert!(lower < higher && negate(higher) < negate(lower));
+        }
+    }
+    // A drawn game is worth as much as an even position
+    assert_eq!(Score::Ended(0), Score::Running(0.0));
+    assert_eq!(Score::Running(-0.0), Score::Running(0.0));
+}
+
+/// Whether `score` is `exact`, give or take the noise.
+fn noisy(score: Score, exact: Score) -> bool {
+    match (score, exact) {
+        (Score::Running(val), Score::Running(exact)) => (val - exact).abs() <= 0.05 * exact.abs() + 1e-9,
+        _ => score == exact,
+    }
+}
+
+#[test]
+fn test_pvs_matches_negamax() {
+    for transcript in ["f5d6c3d3c4f4f6f3e6e7", "f5f6e6f4e3", "c4c3d3c5b4d2e2f3f4", "f5d6c5f4e3f6"].iter() {
+        let (my, opp) = play(transcript);
+        let moves = bit_board::valid_moves_set(my, opp);
+        for &noise in [false, true].iter() {
+            let tt = TranspositionTable::new(1);
+            let mut config = SearchConfig::default();
+            config.set_threads(1);
+            config.noise = noise;
+            let never = Deadline::never();
+            let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &SilentObserver };
+            let mut nnodes = 0;
+            let mut moves_and_scores = Vec::new();
+            for depth in 1 .. 5 {
+                assert!(custom_ai::ai_eval_with_depth(my, opp, depth, moves, &mut moves_and_scores, &ctx,
+                                                      &mut nnodes));
+                // Scores are from the opponent's point of view
+                let exact: Vec<_> = moves_and_scores.iter()
+                    .map(|&(coord, _)| {
+                        let disk = 1u64 << (coord.get_row() * 8 + coord.get_col());
+                        let (nmy, nopp) = bit_board::move_bit_board(my, opp, disk);
+                        negamax(nopp, nmy, depth, &config.evaluator)
+                    })
+                    .collect();
+                let best = exact.iter().cloned().min().unwrap();
+                if noise {
+                    assert!(noisy(moves_and_scores[0].1, exact[0]));
+                    // The best moves get noise too, the others may be bounds
+                    for (&(_, score), &exact) in moves_and_scores.iter().zip(exact.iter()) {
+                        assert!(noisy(score, exact) || (exact != best && score <= exact));
+                    }
+                } else {
+                    assert_eq!(moves_and_scores[0].1, best);
+                    // The other moves are bounds
+                    assert!(moves_and_scores.iter().zip(exact.iter()).all(|(&(_, score), &exact)| score <= exact));
+                }
+            }
+        }
+    }
+}
+
+/// Keeps what the last iteration of a search found.
+struct LastInfo(Mutex<Option<SearchInfo>>);
+
+impl SearchObserver for LastInfo {
+    fn depth_finished(&self, info: &SearchInfo) {
+        *self.0.lock().unwrap() = Some(info.clone());
+    }
+}
+
+#[test]
+fn test_line_from_table() {
+    let mut config = SearchConfig::default();
+    config.set_threads(1);
+    let tt = TranspositionTable::new(1);
+    let never = Deadline::never();
+    let last = LastInfo(Mutex::new(None));
+    let ctx = SearchContext { config: &config, tt: &tt, deadline: &never, observer: &last };
+    let mut nnodes = 0;
+    let mut lines = Vec::new();
+    // The second search finds its lines in the table
+    for _ in 0 .. 2 {
+        let mut moves_and_scores = Vec::new();
+        assert!(custom_ai::ai_eval_with_depth(MY, OPP, 5, bit_board::valid_moves_set(MY, OPP),
+                                              &mut moves_and_scores, &ctx, &mut nnodes));
+        let info = last.0.lock().unwrap().take().expect("The search has finished");
+        lines.push(info.moves[0].line.clone());
+    }
+    assert_eq!(lines[0].len(), 6);
+    assert_eq!(lines[1], lines[0]);
+}